@@ -11,6 +11,7 @@ use axum::{
 use chron_db::{
     models::{EntityKind, EntityVersion, IsoDateTime, PageToken},
     queries::{PaginatedResult, SortOrder},
+    storage::Storage,
 };
 use serde::{
     Deserialize, Deserializer,
@@ -40,6 +41,14 @@ pub async fn get_entities(
     State(ctx): State<AppState>,
     Query(q): Query<GetEntitiesQuery>,
 ) -> Result<Json<PaginatedResult<EntityVersion>>, AppError> {
+    Ok(Json(fetch_entities(&ctx.db, q).await?))
+}
+
+// the handlers are split like this so they work with any Storage, not just postgres
+pub async fn fetch_entities(
+    db: &impl Storage,
+    q: GetEntitiesQuery,
+) -> anyhow::Result<PaginatedResult<EntityVersion>> {
     let default_count = if q.kind == EntityKind::Game {
         100 // games big  
     } else {
@@ -47,21 +56,17 @@ pub async fn get_entities(
     };
     let count = q.count.unwrap_or(default_count).min(1000);
 
-    let events = ctx
-        .db
-        .get_entities(chron_db::queries::GetEntitiesQuery {
-            kind: q.kind,
-            at: q.at.map(|x| x.0),
-            id: q.id,
-            order: q.order,
-            count: count,
-            page: q.page,
-            before: q.before.map(|x| x.0),
-            after: q.after.map(|x| x.0),
-        })
-        .await?;
-
-    Ok(Json(events))
+    db.get_entities(chron_db::queries::GetEntitiesQuery {
+        kind: q.kind,
+        at: q.at.map(|x| x.0),
+        id: q.id,
+        order: q.order,
+        count: count,
+        page: q.page,
+        before: q.before.map(|x| x.0),
+        after: q.after.map(|x| x.0),
+    })
+    .await
 }
 
 #[derive(Deserialize, Debug)]
@@ -83,6 +88,13 @@ pub async fn get_versions(
     State(ctx): State<AppState>,
     Query(q): Query<GetVersionsQuery>,
 ) -> Result<Json<PaginatedResult<EntityVersion>>, AppError> {
+    Ok(Json(fetch_versions(&ctx.db, q).await?))
+}
+
+pub async fn fetch_versions(
+    db: &impl Storage,
+    q: GetVersionsQuery,
+) -> anyhow::Result<PaginatedResult<EntityVersion>> {
     let default_count = if q.kind == EntityKind::Game {
         100 // games big  
     } else {
@@ -90,20 +102,16 @@ pub async fn get_versions(
     };
     let count = q.count.unwrap_or(default_count).min(1000);
 
-    let events = ctx
-        .db
-        .get_versions(chron_db::queries::GetVersionsQuery {
-            kind: q.kind,
-            id: q.id,
-            before: q.before.map(|x| x.0),
-            after: q.after.map(|x| x.0),
-            count: count,
-            order: q.order,
-            page: q.page,
-        })
-        .await?;
-
-    Ok(Json(events))
+    db.get_versions(chron_db::queries::GetVersionsQuery {
+        kind: q.kind,
+        id: q.id,
+        before: q.before.map(|x| x.0),
+        after: q.after.map(|x| x.0),
+        count: count,
+        order: q.order,
+        page: q.page,
+    })
+    .await
 }
 
 pub fn comma_separated<'de, V, T, D>(deserializer: D) -> Result<V, D::Error>
@@ -171,3 +179,109 @@ where
     let visitor = CommaSeparated(PhantomData, PhantomData);
     deserializer.deserialize_str(visitor)
 }
+
+#[cfg(test)]
+mod tests {
+    use chron_db::{models::NewObject, storage::MemoryStorage};
+    use serde_json::json;
+    use time::{Duration, OffsetDateTime, format_description::well_known::Rfc3339};
+
+    use super::*;
+
+    fn at(minutes: i64) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(1_750_000_000).unwrap() + Duration::minutes(minutes)
+    }
+
+    async fn storage() -> MemoryStorage {
+        let storage = MemoryStorage::new();
+        for (entity_id, minutes, name) in [
+            ("a", 0, "first"),
+            ("b", 1, "only"),
+            ("a", 5, "first"),
+            ("a", 10, "second"),
+        ] {
+            storage
+                .save(NewObject {
+                    kind: EntityKind::Team,
+                    entity_id: entity_id.to_string(),
+                    data: json!({ "name": name }),
+                    timestamp: at(minutes),
+                    request_time: Duration::ZERO,
+                })
+                .await
+                .unwrap();
+        }
+        storage
+    }
+
+    fn names(result: &PaginatedResult<EntityVersion>) -> Vec<(String, String)> {
+        result
+            .items
+            .iter()
+            .map(|x| (x.entity_id.clone(), x.data.get().to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn entities_latest_and_at() {
+        let storage = storage().await;
+
+        let q = serde_json::from_value(json!({ "kind": "team" })).unwrap();
+        let latest = fetch_entities(&storage, q).await.unwrap();
+        assert_eq!(
+            names(&latest),
+            [
+                ("b".to_string(), r#"{"name":"only"}"#.to_string()),
+                ("a".to_string(), r#"{"name":"second"}"#.to_string()),
+            ]
+        );
+
+        let q = serde_json::from_value(json!({
+            "kind": "team",
+            "id": "a",
+            "at": at(7).format(&Rfc3339).unwrap(),
+        }))
+        .unwrap();
+        let then = fetch_entities(&storage, q).await.unwrap();
+        assert_eq!(
+            names(&then),
+            [("a".to_string(), r#"{"name":"first"}"#.to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn versions_paginate() {
+        let storage = storage().await;
+
+        let q: GetVersionsQuery = serde_json::from_value(json!({
+            "kind": "team",
+            "id": "a,b",
+            "count": 2,
+        }))
+        .unwrap();
+        let first = fetch_versions(&storage, q).await.unwrap();
+        assert_eq!(
+            names(&first),
+            [
+                ("a".to_string(), r#"{"name":"first"}"#.to_string()),
+                ("b".to_string(), r#"{"name":"only"}"#.to_string()),
+            ]
+        );
+        // same hash again doesn't start a new version
+        assert_eq!(first.items[0].valid_to.as_ref().map(|x| x.0), Some(at(10)));
+
+        let q: GetVersionsQuery = serde_json::from_value(json!({
+            "kind": "team",
+            "id": "a,b",
+            "count": 2,
+            "page": first.next_page.unwrap().to_string(),
+        }))
+        .unwrap();
+        let second = fetch_versions(&storage, q).await.unwrap();
+        assert_eq!(
+            names(&second),
+            [("a".to_string(), r#"{"name":"second"}"#.to_string())]
+        );
+        assert!(second.items[0].valid_to.is_none());
+    }
+}
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use arrow::{
+    array::{ArrayRef, RecordBatch, StringArray},
+    datatypes::Schema,
+    ipc::writer::StreamWriter,
+};
+use axum::{
+    http::{HeaderValue, header},
+    response::{IntoResponse, Response},
+};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+
+// binary, typed formats for loading straight into pandas/polars/duckdb
+// unlike csv/json these aren't streamed, we build the whole batch in memory first
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColumnarFormat {
+    ArrowIpc,
+    Parquet,
+}
+
+impl ColumnarFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            ColumnarFormat::ArrowIpc => "application/vnd.apache.arrow.stream",
+            ColumnarFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    pub fn encode(&self, batch: &RecordBatch) -> anyhow::Result<Vec<u8>> {
+        match self {
+            ColumnarFormat::ArrowIpc => {
+                let mut writer = StreamWriter::try_new(Vec::new(), &batch.schema())?;
+                writer.write(batch)?;
+                writer.finish()?;
+                Ok(writer.into_inner()?)
+            }
+            ColumnarFormat::Parquet => {
+                let props = WriterProperties::builder()
+                    .set_compression(Compression::ZSTD(ZstdLevel::default()))
+                    .build();
+                let mut writer = ArrowWriter::try_new(Vec::new(), batch.schema(), Some(props))?;
+                writer.write(batch)?;
+                Ok(writer.into_inner()?)
+            }
+        }
+    }
+
+    pub async fn into_response(self, batch: RecordBatch) -> anyhow::Result<Response> {
+        // parquet compression on a big result set can take a while, keep it off the runtime
+        let body = tokio::task::spawn_blocking(move || self.encode(&batch)).await??;
+        Ok((
+            [(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(self.content_type()),
+            )],
+            body,
+        )
+            .into_response())
+    }
+}
+
+pub fn string_column<'a, T: 'a>(
+    rows: impl IntoIterator<Item = &'a T>,
+    f: impl Fn(&'a T) -> Option<&'a str>,
+) -> ArrayRef {
+    Arc::new(StringArray::from_iter(rows.into_iter().map(f)))
+}
+
+pub fn record_batch(schema: Schema, columns: Vec<ArrayRef>) -> anyhow::Result<RecordBatch> {
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
@@ -1,4 +1,8 @@
-use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+    str::FromStr,
+};
 
 use axum::{
     Json,
@@ -6,7 +10,7 @@ use axum::{
 };
 use chron_base::normalize_location;
 use chron_db::{
-    derived::{AverageStats, DbGame, DbGamePlayerStats, DbLeague, DbTeam},
+    derived::{AverageStats, DbGame, DbGamePlayerStats, DbLeague, DbTeam, GetPercentilesQuery},
     models::PageToken,
     queries::{PaginatedResult, SortOrder},
 };
@@ -30,10 +34,18 @@ pub struct GetGamesQuery {
     page: Option<PageToken>,
 }
 
+#[derive(Serialize, Debug)]
+pub struct ApiGame {
+    #[serde(flatten)]
+    game: DbGame,
+    // first game ever to end with this score
+    scorigami: bool,
+}
+
 pub async fn get_games(
     State(ctx): State<AppState>,
     Query(q): Query<GetGamesQuery>,
-) -> Result<Json<PaginatedResult<DbGame>>, AppError> {
+) -> Result<Json<PaginatedResult<ApiGame>>, AppError> {
     let games = ctx
         .db
         .get_games(chron_db::derived::GetGamesQuery {
@@ -47,7 +59,19 @@ pub async fn get_games(
         })
         .await?;
 
-    Ok(Json(games))
+    let scorigamis = ctx.scorigami_cache.get(()).await?;
+
+    Ok(Json(PaginatedResult {
+        items: games
+            .items
+            .into_iter()
+            .map(|game| ApiGame {
+                scorigami: scorigamis.contains(&game.game_id),
+                game,
+            })
+            .collect(),
+        next_page: games.next_page,
+    }))
 }
 
 #[derive(Deserialize, Debug)]
@@ -174,30 +198,112 @@ pub async fn locations_inner(ctx: AppState) -> anyhow::Result<Vec<TeamLocation>>
     Ok(teams_augmented)
 }
 
+#[derive(Deserialize, Debug, Default)]
+pub struct ScorigamiQuery {
+    pub season: Option<i32>,
+    pub league: Option<String>,
+    pub team: Option<String>,
+    pub start_day: Option<i32>,
+    pub end_day: Option<i32>,
+    // true for only special day games, false to leave them out
+    pub special: Option<bool>,
+}
+
 #[derive(FromRow, Serialize)]
 pub struct ScorigamiEntry {
     min: i32,
     max: i32,
     count: i32,
     first: String,
+    first_season: i32,
+    first_day: i32,
+    last: String,
+    last_season: i32,
+    last_day: i32,
 }
 
-pub async fn scorigami(State(ctx): State<AppState>) -> Result<Json<Vec<ScorigamiEntry>>, AppError> {
-    let r = fetch_scorigami(&ctx).await?;
+pub async fn scorigami(
+    State(ctx): State<AppState>,
+    Query(q): Query<ScorigamiQuery>,
+) -> Result<Json<Vec<ScorigamiEntry>>, AppError> {
+    let r = fetch_scorigami(&ctx, &q).await?;
     Ok(Json(r))
 }
 
-async fn fetch_scorigami(ctx: &AppState) -> anyhow::Result<Vec<ScorigamiEntry>> {
+async fn fetch_scorigami(
+    ctx: &AppState,
+    q: &ScorigamiQuery,
+) -> anyhow::Result<Vec<ScorigamiEntry>> {
     // inline sql here is a bit nasty but we ball
-    let r = sqlx::query_as(r"with games2 as (select least((last_update->>'home_score')::int, (last_update->>'away_score')::int) as min, greatest((last_update->>'home_score')::int, (last_update->>'away_score')::int) as max, game_id from games where state = 'Complete') select min(game_id) as first, min, max, count(*)::int as count from games2 group by (min, max);")
+    // game ids are objectids so they sort by time, which also puts special days in the right place
+    let r = sqlx::query_as(
+        r"with games2 as (
+            select
+                least((last_update->>'home_score')::int, (last_update->>'away_score')::int) as min,
+                greatest((last_update->>'home_score')::int, (last_update->>'away_score')::int) as max,
+                game_id, season, day
+            from games
+            where state = 'Complete'
+                and ($1::int is null or season = $1)
+                and ($2::text is null or exists (select 1 from teams where teams.team_id in (home_team_id, away_team_id) and teams.league_id = $2))
+                and ($3::text is null or $3 in (home_team_id, away_team_id))
+                and ($4::int is null or day >= $4)
+                and ($5::int is null or day <= $5)
+                and ($6::bool is null or (day_special is not null) = $6)
+        )
+        select
+            min, max, count(*)::int as count,
+            min(game_id) as first,
+            (array_agg(season order by game_id))[1] as first_season,
+            (array_agg(day order by game_id))[1] as first_day,
+            max(game_id) as last,
+            (array_agg(season order by game_id desc))[1] as last_season,
+            (array_agg(day order by game_id desc))[1] as last_day
+        from games2
+        group by (min, max);",
+    )
+    .bind(q.season)
+    .bind(&q.league)
+    .bind(&q.team)
+    .bind(q.start_day)
+    .bind(q.end_day)
+    .bind(q.special)
     .fetch_all(&ctx.db.pool)
     .await?;
     Ok(r)
 }
 
+// the first game ever to end with each score. there's one per distinct score so this stays small
+pub async fn refresh_scorigami_games(ctx: AppState) -> anyhow::Result<HashSet<String>> {
+    info!("refreshing scorigami games");
+    let r: Vec<String> = sqlx::query_scalar(
+        r"with games2 as (
+            select
+                least((last_update->>'home_score')::int, (last_update->>'away_score')::int) as min,
+                greatest((last_update->>'home_score')::int, (last_update->>'away_score')::int) as max,
+                game_id
+            from games
+            where state = 'Complete'
+        )
+        select min(game_id) as game_id from games2 group by (min, max);",
+    )
+    .fetch_all(&ctx.db.pool)
+    .await?;
+    Ok(r.into_iter().collect())
+}
+
+// players need at least this much playing time to count towards the distributions
+pub const DEFAULT_PERCENTILE_MIN_PA: i32 = 20;
+pub const DEFAULT_PERCENTILE_MIN_OUTS: i32 = 30;
+
+// (season, league), None for every league
+pub type PercentileKey = (i32, Option<String>);
+
 #[derive(Clone, Serialize)]
 pub struct LeagueAggregateResponse {
-    leagues: BTreeMap<String, LeagueAggregateLeague>,
+    season: i32,
+    // "all" covers every league at once
+    pub leagues: BTreeMap<String, LeagueAggregateLeague>,
 }
 
 #[derive(Default, Serialize, Clone)]
@@ -222,51 +328,154 @@ pub struct LeagueAggregateStat {
     percentiles: Vec<(f32, f32)>,
 }
 
+impl LeagueAggregateStat {
+    // where a value falls in the distribution, from 0 to 100
+    pub fn rank(&self, value: f32) -> Option<f32> {
+        let (first, last) = (self.percentiles.first()?, self.percentiles.last()?);
+        if value <= first.1 {
+            return Some(first.0 * 100.0);
+        }
+        if value >= last.1 {
+            return Some(last.0 * 100.0);
+        }
+
+        let (lo, hi) = self
+            .percentiles
+            .windows(2)
+            .map(|w| (w[0], w[1]))
+            .find(|(lo, hi)| value >= lo.1 && value < hi.1)?;
+        let frac = (value - lo.1) / (hi.1 - lo.1);
+        Some((lo.0 + frac * (hi.0 - lo.0)) * 100.0)
+    }
+
+    // for stats like fip_const that are the same at every percentile
+    pub fn median(&self) -> Option<f32> {
+        self.percentiles
+            .get(self.percentiles.len() / 2)
+            .map(|x| x.1)
+    }
+}
+
 pub async fn refresh_league_aggregate(
+    key: PercentileKey,
     ctx: AppState,
-) -> anyhow::Result<Vec<LeagueAggregateResponse>> {
-    info!("refreshing league aggregates");
+) -> anyhow::Result<LeagueAggregateResponse> {
+    info!("refreshing league aggregates for {:?}", key);
+    let (season, league) = key;
+    get_league_aggregate(
+        &ctx,
+        GetPercentilesQuery {
+            season,
+            league,
+            start_day: None,
+            end_day: None,
+            min_pa: DEFAULT_PERCENTILE_MIN_PA,
+            min_outs: DEFAULT_PERCENTILE_MIN_OUTS,
+        },
+    )
+    .await
+}
+
+pub async fn get_league_aggregate(
+    ctx: &AppState,
+    q: GetPercentilesQuery,
+) -> anyhow::Result<LeagueAggregateResponse> {
     let mut percentiles = Vec::with_capacity(101);
     for i in 0..=100 {
         percentiles.push((i as f32) / 100.0);
     }
 
-    // todo: don't hardcode season
-    let mut seasons = Vec::new();
-    for season in [0, 1, 2, 3] {
-        let res = ctx.db.get_league_percentiles(&percentiles, season).await?;
-
-        // we should really just "transpose" this logic all the way through...
-        let mut leagues = BTreeMap::new();
-        for entry in res {
-            let league: &mut LeagueAggregateLeague = leagues
-                // todo: make nicer
-                .entry(entry.league_id.unwrap_or_else(|| "null".to_string()))
-                .or_default();
-
-            for (stat, val) in [
-                (&mut league.ba, entry.ba),
-                (&mut league.obp, entry.obp),
-                (&mut league.slg, entry.slg),
-                (&mut league.ops, entry.ops),
-                (&mut league.sb_success, entry.sb_success),
-                (&mut league.era, entry.era),
-                (&mut league.whip, entry.whip),
-                (&mut league.fip_base, entry.fip_base),
-                (&mut league.fip_const, entry.fip_const),
-                (&mut league.h9, entry.h9),
-                (&mut league.k9, entry.k9),
-                (&mut league.bb9, entry.bb9),
-                (&mut league.hr9, entry.hr9),
-            ] {
+    let single_league = q.league.is_some();
+    let res = ctx.db.get_league_percentiles(&percentiles, &q).await?;
+
+    // we should really just "transpose" this logic all the way through...
+    let mut leagues = BTreeMap::new();
+    for entry in res {
+        let key = match (entry.all_leagues, entry.league_id) {
+            // same thing as the league itself
+            (true, _) if single_league => continue,
+            (true, _) => "all".to_string(),
+            (false, Some(league_id)) => league_id,
+            // teams we don't know the league of
+            (false, None) => continue,
+        };
+        let league: &mut LeagueAggregateLeague = leagues.entry(key).or_default();
+
+        for (stat, val) in [
+            (&mut league.ba, entry.ba),
+            (&mut league.obp, entry.obp),
+            (&mut league.slg, entry.slg),
+            (&mut league.ops, entry.ops),
+            (&mut league.sb_success, entry.sb_success),
+            (&mut league.era, entry.era),
+            (&mut league.whip, entry.whip),
+            (&mut league.fip_base, entry.fip_base),
+            (&mut league.fip_const, entry.fip_const),
+            (&mut league.h9, entry.h9),
+            (&mut league.k9, entry.k9),
+            (&mut league.bb9, entry.bb9),
+            (&mut league.hr9, entry.hr9),
+        ] {
+            if let Some(val) = val {
                 stat.percentiles.push((entry.percentile, val));
             }
         }
+    }
 
-        seasons.push(LeagueAggregateResponse { leagues });
+    Ok(LeagueAggregateResponse {
+        season: q.season,
+        leagues,
+    })
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PercentilesQuery {
+    // every season we have games for if not given
+    pub season: Option<i32>,
+    pub league: Option<String>,
+    pub start_day: Option<i32>,
+    pub end_day: Option<i32>,
+    pub min_pa: Option<i32>,
+    pub min_outs: Option<i32>,
+}
+
+pub async fn percentiles(
+    State(ctx): State<AppState>,
+    Query(q): Query<PercentilesQuery>,
+) -> Result<Json<Vec<LeagueAggregateResponse>>, AppError> {
+    let seasons = match q.season {
+        Some(season) => vec![season],
+        None => ctx.db.get_percentile_seasons().await?,
+    };
+
+    // only the plain whole-season queries go through the cache
+    let custom =
+        q.start_day.is_some() || q.end_day.is_some() || q.min_pa.is_some() || q.min_outs.is_some();
+
+    let mut res = Vec::with_capacity(seasons.len());
+    for season in seasons {
+        if custom {
+            res.push(
+                get_league_aggregate(
+                    &ctx,
+                    GetPercentilesQuery {
+                        season,
+                        league: q.league.clone(),
+                        start_day: q.start_day,
+                        end_day: q.end_day,
+                        min_pa: q.min_pa.unwrap_or(DEFAULT_PERCENTILE_MIN_PA),
+                        min_outs: q.min_outs.unwrap_or(DEFAULT_PERCENTILE_MIN_OUTS),
+                    },
+                )
+                .await?,
+            );
+        } else {
+            let cached = ctx.percentile_cache.get((season, q.league.clone())).await?;
+            res.push((*cached).clone());
+        }
     }
 
-    Ok(seasons)
+    Ok(Json(res))
 }
 
 #[derive(Serialize, Debug)]
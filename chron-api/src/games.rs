@@ -0,0 +1,338 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use chron_base::{StatKey, objectid_to_timestamp};
+use chron_db::{
+    derived::{DbHalfInningScore, GamePlayerLine},
+    models::EntityKind,
+};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::{AppError, AppState};
+
+#[derive(Serialize, Debug)]
+pub struct BoxScore {
+    game_id: String,
+    season: i32,
+    day: i32,
+    day_special: Option<String>,
+    state: String,
+    line_score: Vec<LineScoreInning>,
+    away: TeamBoxScore,
+    home: TeamBoxScore,
+}
+
+// None means the half-inning wasn't played (yet)
+#[derive(Serialize, Debug)]
+pub struct LineScoreInning {
+    inning: i32,
+    away: Option<i32>,
+    home: Option<i32>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TeamBoxScore {
+    team_id: String,
+    name: Option<String>,
+    abbreviation: Option<String>,
+    emoji: Option<String>,
+    runs: Option<i32>,
+    hits: u32,
+    errors: u32,
+    left_on_base: u32,
+    batting: Vec<BattingLine>,
+    batting_totals: BattingLine,
+    pitching: Vec<PitchingLine>,
+    pitching_totals: PitchingLine,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct BattingLine {
+    player_id: Option<String>,
+    name: Option<String>,
+    slot: Option<String>,
+    ab: u32,
+    r: u32,
+    h: u32,
+    rbi: u32,
+    bb: u32,
+    so: u32,
+    hr: u32,
+    sb: u32,
+    lob: u32,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct PitchingLine {
+    player_id: Option<String>,
+    name: Option<String>,
+    slot: Option<String>,
+    // in the usual "6.2" notation
+    ip: String,
+    outs: u32,
+    h: u32,
+    r: u32,
+    er: u32,
+    bb: u32,
+    so: u32,
+    hr: u32,
+    pitches: u32,
+    batters_faced: u32,
+    decision: Option<&'static str>,
+}
+
+// just the bits of the team object we care about
+#[derive(Deserialize)]
+struct TeamAtGameTime {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Location")]
+    location: String,
+    #[serde(rename = "Abbreviation", default)]
+    abbreviation: Option<String>,
+    #[serde(rename = "Emoji", default)]
+    emoji: Option<String>,
+    #[serde(rename = "Players", default)]
+    players: Vec<TeamSlotAtGameTime>,
+}
+
+#[derive(Deserialize)]
+struct TeamSlotAtGameTime {
+    #[serde(rename = "PlayerID")]
+    player_id: String,
+    #[serde(rename = "Slot", default)]
+    slot: Option<String>,
+}
+
+pub async fn boxscore(
+    State(ctx): State<AppState>,
+    Path(game_id): Path<String>,
+) -> Result<Json<BoxScore>, AppError> {
+    let Some(game) = ctx.db.get_game(&game_id).await? else {
+        return Err(AppError::not_found("game not found"));
+    };
+
+    let lines = ctx.db.get_game_player_lines(&game_id).await?;
+    let half_innings = ctx.db.get_game_half_inning_scores(&game_id).await?;
+
+    // game ids are objectids, so this is roughly when the game was scheduled
+    let game_time = objectid_to_timestamp(&game_id).ok();
+    let away_team = get_team_at(&ctx, &game.away_team_id, game_time).await?;
+    let home_team = get_team_at(&ctx, &game.home_team_id, game_time).await?;
+
+    let score = |key: &str| {
+        game.last_update
+            .as_ref()
+            .and_then(|x| x.get(key))
+            .and_then(|x| x.as_i64())
+            .map(|x| x as i32)
+    };
+
+    let away = team_box_score(
+        &game.away_team_id,
+        away_team.as_ref(),
+        score("away_score"),
+        &lines,
+    );
+    let home = team_box_score(
+        &game.home_team_id,
+        home_team.as_ref(),
+        score("home_score"),
+        &lines,
+    );
+
+    Ok(Json(BoxScore {
+        line_score: line_score(&half_innings),
+        game_id: game.game_id,
+        season: game.season,
+        day: game.day,
+        day_special: game.day_special,
+        state: game.state,
+        away,
+        home,
+    }))
+}
+
+async fn get_team_at(
+    ctx: &AppState,
+    team_id: &str,
+    at: Option<OffsetDateTime>,
+) -> anyhow::Result<Option<TeamAtGameTime>> {
+    let version = match at {
+        Some(at) => ctx.db.get_entity_at(EntityKind::Team, team_id, &at).await?,
+        None => None,
+    };
+    let version = match version {
+        Some(version) => Some(version),
+        // team didn't exist yet by our records, the current one is better than nothing
+        None => ctx.db.get_latest(EntityKind::Team, team_id).await?,
+    };
+
+    Ok(version.and_then(|x| x.parse().ok()))
+}
+
+fn line_score(half_innings: &[DbHalfInningScore]) -> Vec<LineScoreInning> {
+    let mut innings: Vec<LineScoreInning> = Vec::new();
+    let (mut away_total, mut home_total) = (0, 0);
+    for half in half_innings.iter().filter(|x| x.inning >= 1) {
+        let idx = match innings.iter().position(|x| x.inning == half.inning) {
+            Some(idx) => idx,
+            None => {
+                innings.push(LineScoreInning {
+                    inning: half.inning,
+                    away: None,
+                    home: None,
+                });
+                innings.len() - 1
+            }
+        };
+
+        // side 0 is the top of the inning, away team batting
+        if half.inning_side == 0 {
+            let score = half.away_score.unwrap_or(away_total);
+            innings[idx].away = Some(score - away_total);
+            away_total = score;
+        } else {
+            let score = half.home_score.unwrap_or(home_total);
+            innings[idx].home = Some(score - home_total);
+            home_total = score;
+        }
+    }
+    innings
+}
+
+fn team_box_score(
+    team_id: &str,
+    team: Option<&TeamAtGameTime>,
+    runs: Option<i32>,
+    lines: &[GamePlayerLine],
+) -> TeamBoxScore {
+    let slot_of = |player_id: &str| {
+        team.and_then(|t| t.players.iter().position(|p| p.player_id == player_id))
+    };
+
+    let mut team_lines: Vec<&GamePlayerLine> =
+        lines.iter().filter(|x| x.team_id == team_id).collect();
+    // roster order is lineup order, then the rotation/bullpen
+    team_lines.sort_by_key(|x| {
+        (
+            slot_of(&x.player_id).unwrap_or(usize::MAX),
+            x.player_name.clone(),
+        )
+    });
+
+    let slot_name = |player_id: &str| {
+        slot_of(player_id).and_then(|idx| team.and_then(|t| t.players[idx].slot.clone()))
+    };
+
+    let mut batting = Vec::new();
+    let mut batting_totals = BattingLine::default();
+    let mut pitching = Vec::new();
+    let mut pitching_totals = PitchingLine::default();
+    let mut errors = 0;
+    for line in team_lines {
+        errors += line.get(StatKey::Errors);
+
+        if line.get(StatKey::PlateAppearances) > 0 {
+            let batting_line = BattingLine {
+                player_id: Some(line.player_id.clone()),
+                name: line.player_name.clone(),
+                slot: slot_name(&line.player_id),
+                ab: line.get(StatKey::AtBats),
+                r: line.get(StatKey::Runs),
+                h: hits(line),
+                rbi: line.get(StatKey::RunsBattedIn),
+                bb: line.get(StatKey::Walked),
+                so: line.get(StatKey::StruckOut),
+                hr: line.get(StatKey::HomeRuns),
+                sb: line.get(StatKey::StolenBases),
+                lob: line.get(StatKey::LeftOnBase),
+            };
+            add_batting(&mut batting_totals, &batting_line);
+            batting.push(batting_line);
+        }
+
+        if line.get(StatKey::BattersFaced) > 0 {
+            let outs = line.get(StatKey::Outs);
+            let pitching_line = PitchingLine {
+                player_id: Some(line.player_id.clone()),
+                name: line.player_name.clone(),
+                slot: slot_name(&line.player_id),
+                ip: innings_pitched(outs),
+                outs,
+                h: line.get(StatKey::HitsAllowed),
+                r: line.get(StatKey::EarnedRuns) + line.get(StatKey::UnearnedRuns),
+                er: line.get(StatKey::EarnedRuns),
+                bb: line.get(StatKey::Walks),
+                so: line.get(StatKey::Strikeouts),
+                hr: line.get(StatKey::HomeRunsAllowed),
+                pitches: line.get(StatKey::PitchesThrown),
+                batters_faced: line.get(StatKey::BattersFaced),
+                decision: if line.get(StatKey::Wins) > 0 {
+                    Some("W")
+                } else if line.get(StatKey::Losses) > 0 {
+                    Some("L")
+                } else if line.get(StatKey::Saves) > 0 {
+                    Some("S")
+                } else {
+                    None
+                },
+            };
+            add_pitching(&mut pitching_totals, &pitching_line);
+            pitching.push(pitching_line);
+        }
+    }
+    pitching_totals.ip = innings_pitched(pitching_totals.outs);
+
+    TeamBoxScore {
+        team_id: team_id.to_string(),
+        name: team.map(|t| format!("{} {}", t.location, t.name)),
+        abbreviation: team.and_then(|t| t.abbreviation.clone()),
+        emoji: team.and_then(|t| t.emoji.clone()),
+        runs,
+        hits: batting_totals.h,
+        errors,
+        left_on_base: batting_totals.lob,
+        batting,
+        batting_totals,
+        pitching,
+        pitching_totals,
+    }
+}
+
+fn hits(line: &GamePlayerLine) -> u32 {
+    line.get(StatKey::Singles)
+        + line.get(StatKey::Doubles)
+        + line.get(StatKey::Triples)
+        + line.get(StatKey::HomeRuns)
+}
+
+fn innings_pitched(outs: u32) -> String {
+    format!("{}.{}", outs / 3, outs % 3)
+}
+
+fn add_batting(totals: &mut BattingLine, line: &BattingLine) {
+    totals.ab += line.ab;
+    totals.r += line.r;
+    totals.h += line.h;
+    totals.rbi += line.rbi;
+    totals.bb += line.bb;
+    totals.so += line.so;
+    totals.hr += line.hr;
+    totals.sb += line.sb;
+    totals.lob += line.lob;
+}
+
+fn add_pitching(totals: &mut PitchingLine, line: &PitchingLine) {
+    totals.outs += line.outs;
+    totals.h += line.h;
+    totals.r += line.r;
+    totals.er += line.er;
+    totals.bb += line.bb;
+    totals.so += line.so;
+    totals.hr += line.hr;
+    totals.pitches += line.pitches;
+    totals.batters_faced += line.batters_faced;
+}
@@ -0,0 +1,311 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use chron_db::derived::LeaderTotals;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{AppError, AppState, chron_api::comma_separated2};
+
+// the usual mlb qualifiers, 3.1 PA and 1 IP per team game
+const PA_PER_TEAM_GAME: f64 = 3.1;
+const OUTS_PER_TEAM_GAME: f64 = 3.0;
+
+// (season, league), None for every league
+pub type LeadersKey = (i32, Option<String>);
+
+#[derive(Clone)]
+pub struct LeaderPool {
+    players: Vec<LeaderTotals>,
+    fip_const: f64,
+}
+
+pub async fn refresh_leaders(key: LeadersKey, ctx: AppState) -> anyhow::Result<LeaderPool> {
+    info!("refreshing leaders for {:?}", key);
+    let (season, league) = key;
+    let players = ctx.db.get_leader_totals(season, league.as_deref()).await?;
+
+    // league era minus league "fip without the constant"
+    let (mut outs, mut er, mut hr, mut bb, mut hbp, mut k) = (0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+    for p in &players {
+        outs += p.outs as f64;
+        er += p.earned_runs as f64;
+        hr += p.home_runs_allowed as f64;
+        bb += p.walks as f64;
+        hbp += p.hit_batters as f64;
+        k += p.strikeouts as f64;
+    }
+    let fip_const = if outs > 0.0 {
+        27.0 * er / outs - 3.0 * (13.0 * hr + 3.0 * (bb + hbp) - 2.0 * k) / outs
+    } else {
+        0.0
+    };
+
+    Ok(LeaderPool { players, fip_const })
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+enum StatKind {
+    Batting,
+    Pitching,
+}
+
+struct LeaderStat {
+    name: &'static str,
+    kind: StatKind,
+    // rate stats only count qualified players
+    rate: bool,
+    lower_is_better: bool,
+    value: fn(&LeaderTotals, f64) -> Option<f64>,
+}
+
+const fn counting(
+    name: &'static str,
+    kind: StatKind,
+    value: fn(&LeaderTotals, f64) -> Option<f64>,
+) -> LeaderStat {
+    LeaderStat {
+        name,
+        kind,
+        rate: false,
+        lower_is_better: false,
+        value,
+    }
+}
+
+const fn rate(
+    name: &'static str,
+    kind: StatKind,
+    lower_is_better: bool,
+    value: fn(&LeaderTotals, f64) -> Option<f64>,
+) -> LeaderStat {
+    LeaderStat {
+        name,
+        kind,
+        rate: true,
+        lower_is_better,
+        value,
+    }
+}
+
+fn hits(p: &LeaderTotals) -> f64 {
+    (p.singles + p.doubles + p.triples + p.home_runs) as f64
+}
+
+fn total_bases(p: &LeaderTotals) -> f64 {
+    (p.singles + 2 * p.doubles + 3 * p.triples + 4 * p.home_runs) as f64
+}
+
+fn ratio(num: f64, denom: f64) -> Option<f64> {
+    if denom > 0.0 { Some(num / denom) } else { None }
+}
+
+fn obp(p: &LeaderTotals) -> Option<f64> {
+    ratio(
+        hits(p) + (p.walked + p.hit_by_pitch) as f64,
+        (p.at_bats + p.walked + p.hit_by_pitch + p.sac_flies) as f64,
+    )
+}
+
+fn per_nine(value: i32, p: &LeaderTotals) -> Option<f64> {
+    ratio(27.0 * value as f64, p.outs as f64)
+}
+
+const STATS: &[LeaderStat] = &[
+    rate("ba", StatKind::Batting, false, |p, _| {
+        ratio(hits(p), p.at_bats as f64)
+    }),
+    rate("obp", StatKind::Batting, false, |p, _| obp(p)),
+    rate("slg", StatKind::Batting, false, |p, _| {
+        ratio(total_bases(p), p.at_bats as f64)
+    }),
+    rate("ops", StatKind::Batting, false, |p, _| {
+        Some(obp(p)? + ratio(total_bases(p), p.at_bats as f64)?)
+    }),
+    rate("babip", StatKind::Batting, false, |p, _| {
+        ratio(
+            hits(p) - p.home_runs as f64,
+            (p.at_bats - p.struck_out - p.home_runs + p.sac_flies) as f64,
+        )
+    }),
+    counting("hits", StatKind::Batting, |p, _| Some(hits(p))),
+    counting("singles", StatKind::Batting, |p, _| Some(p.singles as f64)),
+    counting("doubles", StatKind::Batting, |p, _| Some(p.doubles as f64)),
+    counting("triples", StatKind::Batting, |p, _| Some(p.triples as f64)),
+    counting("home_runs", StatKind::Batting, |p, _| {
+        Some(p.home_runs as f64)
+    }),
+    counting("runs", StatKind::Batting, |p, _| Some(p.runs as f64)),
+    counting("runs_batted_in", StatKind::Batting, |p, _| {
+        Some(p.runs_batted_in as f64)
+    }),
+    counting("walked", StatKind::Batting, |p, _| Some(p.walked as f64)),
+    counting("struck_out", StatKind::Batting, |p, _| {
+        Some(p.struck_out as f64)
+    }),
+    counting("stolen_bases", StatKind::Batting, |p, _| {
+        Some(p.stolen_bases as f64)
+    }),
+    counting("caught_stealing", StatKind::Batting, |p, _| {
+        Some(p.caught_stealing as f64)
+    }),
+    counting("hit_by_pitch", StatKind::Batting, |p, _| {
+        Some(p.hit_by_pitch as f64)
+    }),
+    rate("era", StatKind::Pitching, true, |p, _| {
+        per_nine(p.earned_runs, p)
+    }),
+    rate("whip", StatKind::Pitching, true, |p, _| {
+        ratio(3.0 * (p.walks + p.hits_allowed) as f64, p.outs as f64)
+    }),
+    rate("fip", StatKind::Pitching, true, |p, fip_const| {
+        let base = ratio(
+            3.0 * (13 * p.home_runs_allowed + 3 * (p.walks + p.hit_batters) - 2 * p.strikeouts)
+                as f64,
+            p.outs as f64,
+        )?;
+        Some(base + fip_const)
+    }),
+    rate("h9", StatKind::Pitching, true, |p, _| {
+        per_nine(p.hits_allowed, p)
+    }),
+    rate("k9", StatKind::Pitching, false, |p, _| {
+        per_nine(p.strikeouts, p)
+    }),
+    rate("bb9", StatKind::Pitching, true, |p, _| per_nine(p.walks, p)),
+    rate("hr9", StatKind::Pitching, true, |p, _| {
+        per_nine(p.home_runs_allowed, p)
+    }),
+    counting("strikeouts", StatKind::Pitching, |p, _| {
+        Some(p.strikeouts as f64)
+    }),
+    counting("wins", StatKind::Pitching, |p, _| Some(p.wins as f64)),
+    counting("saves", StatKind::Pitching, |p, _| Some(p.saves as f64)),
+    counting("outs", StatKind::Pitching, |p, _| Some(p.outs as f64)),
+    counting("hit_batters", StatKind::Pitching, |p, _| {
+        Some(p.hit_batters as f64)
+    }),
+];
+
+#[derive(Deserialize, Debug)]
+pub struct LeadersQuery {
+    pub season: i32,
+    pub league: Option<String>,
+    pub count: Option<usize>,
+    // every stat if empty
+    #[serde(deserialize_with = "comma_separated2", default)]
+    pub stats: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LeadersResponse {
+    season: i32,
+    league: Option<String>,
+    min_pa: f64,
+    min_outs: f64,
+    leaderboards: Vec<Leaderboard>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Leaderboard {
+    stat: &'static str,
+    kind: StatKind,
+    leaders: Vec<LeaderEntry>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LeaderEntry {
+    // tied players share a rank, so this can skip numbers
+    rank: usize,
+    player_id: String,
+    player_name: Option<String>,
+    team_id: Option<String>,
+    value: f64,
+    plate_appearances: i32,
+    outs: i32,
+}
+
+pub async fn leaders(
+    State(ctx): State<AppState>,
+    Query(q): Query<LeadersQuery>,
+) -> Result<Json<LeadersResponse>, AppError> {
+    let count = q.count.unwrap_or(10).clamp(1, 100);
+    let pool = ctx.leaders_cache.get((q.season, q.league.clone())).await?;
+
+    let mut leaderboards = Vec::new();
+    for stat in STATS {
+        if !q.stats.is_empty() && !q.stats.iter().any(|s| s == stat.name) {
+            continue;
+        }
+
+        let mut values: Vec<(&LeaderTotals, f64)> = pool
+            .players
+            .iter()
+            .filter(|p| !stat.rate || qualified(p, stat.kind))
+            .filter_map(|p| Some((p, (stat.value)(p, pool.fip_const)?)))
+            // nobody needs to see a leaderboard full of zeroes
+            .filter(|(_, value)| stat.rate || *value > 0.0)
+            .collect();
+        values.sort_by(|a, b| {
+            let ord = if stat.lower_is_better {
+                a.1.total_cmp(&b.1)
+            } else {
+                b.1.total_cmp(&a.1)
+            };
+            ord.then_with(|| a.0.player_id.cmp(&b.0.player_id))
+        });
+
+        let mut leaders: Vec<LeaderEntry> = Vec::new();
+        for (i, (p, value)) in values.into_iter().enumerate() {
+            let rank = match leaders.last() {
+                Some(last) if last.value == value => last.rank,
+                _ => i + 1,
+            };
+            // keep going past the count as long as people are tied for the last spot
+            if rank > count {
+                break;
+            }
+
+            leaders.push(LeaderEntry {
+                rank,
+                player_id: p.player_id.clone(),
+                player_name: p.player_name.clone(),
+                team_id: p.team_id.clone(),
+                value,
+                plate_appearances: p.plate_appearances,
+                outs: p.outs,
+            });
+        }
+
+        leaderboards.push(Leaderboard {
+            stat: stat.name,
+            kind: stat.kind,
+            leaders,
+        });
+    }
+
+    // most games played by any team, just so there's something to show for the thresholds
+    let max_team_games = pool.players.iter().map(|p| p.team_games).max().unwrap_or(0) as f64;
+
+    Ok(Json(LeadersResponse {
+        season: q.season,
+        league: q.league,
+        min_pa: (max_team_games * PA_PER_TEAM_GAME).round(),
+        min_outs: (max_team_games * OUTS_PER_TEAM_GAME).round(),
+        leaderboards,
+    }))
+}
+
+fn qualified(p: &LeaderTotals, kind: StatKind) -> bool {
+    match kind {
+        StatKind::Batting => {
+            p.plate_appearances > 0
+                && p.plate_appearances as f64 >= p.team_games as f64 * PA_PER_TEAM_GAME
+        }
+        StatKind::Pitching => {
+            p.outs > 0 && p.outs as f64 >= p.team_games as f64 * OUTS_PER_TEAM_GAME
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashSet, sync::Arc, time::Duration};
 
 use axum::{
     Router,
@@ -8,8 +8,12 @@ use axum::{
 };
 use chron_base::{ChronConfig, cache::SwrCache2, load_config, stop_signal};
 use chron_db::ChronDb;
-use derived_api::{LeagueAggregateResponse, refresh_league_aggregate};
+use derived_api::{
+    LeagueAggregateResponse, PercentileKey, refresh_league_aggregate, refresh_scorigami_games,
+};
 // use polars::enable_string_cache;
+use leaders::{LeaderPool, LeadersKey, refresh_leaders};
+use tokio::sync::Semaphore;
 use tower_http::{
     compression::CompressionLayer,
     cors::{Any, CorsLayer},
@@ -17,29 +21,52 @@ use tower_http::{
     trace::{DefaultOnRequest, DefaultOnResponse, TraceLayer},
 };
 use tracing::info;
+use win_probability::{WinExpectancyTable, refresh_win_expectancy};
 
 mod chron_api;
+mod columnar;
 mod derived_api;
+mod games;
+mod leaders;
+mod matchups;
+mod pitches;
+mod players;
+mod query;
+mod re24;
+mod standings;
 mod stats;
+mod teams;
+mod win_probability;
 
 #[derive(Clone)]
 pub struct AppState {
     config: Arc<ChronConfig>,
     db: ChronDb,
-    percentile_cache: SwrCache2<(), Vec<LeagueAggregateResponse>, AppState>,
+    percentile_cache: SwrCache2<PercentileKey, LeagueAggregateResponse, AppState>,
+    win_expectancy_cache: SwrCache2<(), WinExpectancyTable, AppState>,
+    leaders_cache: SwrCache2<LeadersKey, LeaderPool, AppState>,
+    scorigami_cache: SwrCache2<(), HashSet<String>, AppState>,
+    query_semaphore: Arc<Semaphore>,
 }
 
-pub struct AppError(anyhow::Error);
+pub struct AppError(StatusCode, anyhow::Error);
+
+impl AppError {
+    // for ids that don't exist, everything else coming through `?` is a 500
+    pub fn not_found(message: &'static str) -> AppError {
+        AppError(StatusCode::NOT_FOUND, anyhow::anyhow!(message))
+    }
+}
 
 impl From<anyhow::Error> for AppError {
     fn from(e: anyhow::Error) -> Self {
-        AppError(e)
+        AppError(StatusCode::INTERNAL_SERVER_ERROR, e)
     }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        (StatusCode::INTERNAL_SERVER_ERROR, self.0.to_string()).into_response()
+        (self.0, self.1.to_string()).into_response()
     }
 }
 
@@ -52,12 +79,25 @@ async fn main() -> anyhow::Result<()> {
 
     let state = AppState {
         db,
-        percentile_cache: SwrCache2::new(Duration::from_secs(60 * 10), 10, move |_, ctx| {
-            refresh_league_aggregate(ctx)
+        percentile_cache: SwrCache2::new(Duration::from_secs(60 * 10), 100, move |key, ctx| {
+            refresh_league_aggregate(key, ctx)
+        }),
+        win_expectancy_cache: SwrCache2::new(Duration::from_secs(60 * 60), 1, move |_, ctx| {
+            refresh_win_expectancy(ctx)
+        }),
+        leaders_cache: SwrCache2::new(Duration::from_secs(60 * 10), 100, move |key, ctx| {
+            refresh_leaders(key, ctx)
+        }),
+        scorigami_cache: SwrCache2::new(Duration::from_secs(60 * 10), 1, move |_, ctx| {
+            refresh_scorigami_games(ctx)
         }),
+        query_semaphore: Arc::new(Semaphore::new(query::MAX_CONCURRENT_QUERIES)),
         config: Arc::new(config),
     };
     state.percentile_cache.set_context(state.clone());
+    state.win_expectancy_cache.set_context(state.clone());
+    state.leaders_cache.set_context(state.clone());
+    state.scorigami_cache.set_context(state.clone());
 
     let cors = CorsLayer::new()
         .allow_methods([Method::GET])
@@ -71,9 +111,27 @@ async fn main() -> anyhow::Result<()> {
         .route("/chron/v0/entities", get(chron_api::get_entities))
         .route("/chron/v0/versions", get(chron_api::get_versions))
         .route("/games", get(derived_api::get_games))
+        .route("/games/{id}/boxscore", get(games::boxscore))
+        .route("/games/{id}/pitches", get(pitches::game_pitches))
+        .route(
+            "/games/{id}/win-probability",
+            get(win_probability::game_win_probability),
+        )
         .route("/teams", get(derived_api::get_teams))
+        .route("/teams/{id}/schedule", get(teams::schedule))
+        .route("/teams/{id}/vs/{other}", get(teams::head_to_head))
+        .route("/leaders", get(leaders::leaders))
         .route("/leagues", get(derived_api::get_leagues))
+        .route("/matchups", get(matchups::matchups))
+        .route("/pitch-stats", get(pitches::pitch_stats))
         .route("/player-stats", get(derived_api::get_player_stats))
+        .route("/players/{id}/gamelog", get(players::gamelog))
+        .route("/players/{id}/percentiles", get(players::percentiles))
+        .route("/standings", get(standings::standings))
+        .route("/query", get(query::query))
+        .route("/re24", get(re24::re24))
+        .route("/wpa", get(win_probability::wpa))
+        .route("/percentiles", get(derived_api::percentiles))
         .route("/scorigami", get(derived_api::scorigami))
         .route("/locations", get(derived_api::locations))
         .route("/stats", get(stats::stats));
@@ -0,0 +1,78 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use chron_db::derived::{GetMatchupsQuery, MatchupRow};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppError, AppState, derived_api::SeasonDay};
+
+#[derive(Deserialize, Debug)]
+pub struct MatchupsRequest {
+    pub batter: Option<String>,
+    pub pitcher: Option<String>,
+
+    pub season: Option<i32>,
+    pub start: Option<SeasonDay>,
+    pub end: Option<SeasonDay>,
+
+    // one row total instead of one per opponent
+    #[serde(default)]
+    pub combined: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MatchupResponseRow {
+    #[serde(flatten)]
+    row: MatchupRow,
+    avg: Option<f64>,
+    obp: Option<f64>,
+    slg: Option<f64>,
+}
+
+impl From<MatchupRow> for MatchupResponseRow {
+    fn from(row: MatchupRow) -> Self {
+        let ratio = |num: i32, den: i32| {
+            if den > 0 {
+                Some(num as f64 / den as f64)
+            } else {
+                None
+            }
+        };
+
+        let total_bases = row.h + row.doubles + 2 * row.triples + 3 * row.hr;
+        MatchupResponseRow {
+            avg: ratio(row.h, row.ab),
+            obp: ratio(row.h + row.bb + row.hbp, row.ab + row.bb + row.hbp + row.sf),
+            slg: ratio(total_bases, row.ab),
+            row,
+        }
+    }
+}
+
+pub async fn matchups(
+    State(ctx): State<AppState>,
+    Query(mut q): Query<MatchupsRequest>,
+) -> Result<Json<Vec<MatchupResponseRow>>, AppError> {
+    if q.batter.is_none() && q.pitcher.is_none() {
+        return Err(anyhow::anyhow!("must include either batter or pitcher id").into());
+    }
+
+    if let Some(season) = q.season {
+        q.start = Some(SeasonDay::new(season, 0));
+        q.end = Some(SeasonDay::new(season + 1, 0));
+    }
+
+    let rows = ctx
+        .db
+        .get_matchups(GetMatchupsQuery {
+            batter: q.batter,
+            pitcher: q.pitcher,
+            start: q.start.map(Into::into),
+            end: q.end.map(Into::into),
+            combined: q.combined,
+        })
+        .await?;
+
+    Ok(Json(rows.into_iter().map(Into::into).collect()))
+}
@@ -0,0 +1,65 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use chron_db::derived::{DbPitch, GetPitchStatsQuery, PitchStatsRow};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppError, AppState};
+
+#[derive(Serialize, Debug)]
+pub struct GamePitchesResponse {
+    game_id: String,
+    pitches: Vec<DbPitch>,
+}
+
+pub async fn game_pitches(
+    State(ctx): State<AppState>,
+    Path(game_id): Path<String>,
+) -> Result<Json<GamePitchesResponse>, AppError> {
+    let pitches = ctx.db.get_game_pitches(&game_id).await?;
+    Ok(Json(GamePitchesResponse { game_id, pitches }))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PitchStatsQuery {
+    pub season: i32,
+    #[serde(default)]
+    pub pitcher: Option<String>,
+    #[serde(default)]
+    pub by_type: bool,
+    #[serde(default)]
+    pub min_pitches: Option<i64>,
+    #[serde(default)]
+    pub count: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PitchStatsResponse {
+    season: i32,
+    pitchers: Vec<PitchStatsRow>,
+}
+
+pub async fn pitch_stats(
+    State(ctx): State<AppState>,
+    Query(q): Query<PitchStatsQuery>,
+) -> Result<Json<PitchStatsResponse>, AppError> {
+    let pitchers = ctx
+        .db
+        .get_pitch_stats(GetPitchStatsQuery {
+            season: q.season,
+            // a single pitcher doesn't need a qualifier
+            min_pitches: q
+                .min_pitches
+                .unwrap_or(if q.pitcher.is_some() { 0 } else { 100 }),
+            pitcher: q.pitcher,
+            by_type: q.by_type,
+            count: q.count.unwrap_or(100),
+        })
+        .await?;
+
+    Ok(Json(PitchStatsResponse {
+        season: q.season,
+        pitchers,
+    }))
+}
@@ -0,0 +1,297 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use arrow::{
+    array::{ArrayRef, BooleanArray, Int16Array, Int32Array, UInt32Array},
+    datatypes::{DataType, Field, Schema},
+};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    response::Response,
+};
+use chron_base::StatKey;
+use chron_db::derived::{GameLogRow, GetGameLogQuery, GetPercentilesQuery};
+use serde::{Deserialize, Serialize, Serializer, ser::SerializeStruct};
+use strum::{EnumCount, VariantArray};
+
+use crate::{
+    AppError, AppState,
+    chron_api::comma_separated2,
+    columnar,
+    derived_api::{
+        DEFAULT_PERCENTILE_MIN_OUTS, DEFAULT_PERCENTILE_MIN_PA, LeagueAggregateLeague,
+        LeagueAggregateStat, SeasonDay, get_league_aggregate,
+    },
+    stats::{StatsFormat, table_response},
+};
+
+#[derive(Deserialize, Debug)]
+pub struct GameLogRequest {
+    pub season: Option<i32>,
+    pub start: Option<SeasonDay>,
+    pub end: Option<SeasonDay>,
+
+    // defaults to every stat
+    #[serde(deserialize_with = "comma_separated2", default)]
+    pub fields: Vec<StatKey>,
+
+    pub format: Option<StatsFormat>,
+}
+
+struct GameLogOutputRow {
+    row: GameLogRow,
+    fields: Arc<Vec<StatKey>>,
+}
+
+impl Serialize for GameLogOutputRow {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("GameLogRow", 12 + self.fields.len())?;
+        state.serialize_field("game_id", &self.row.game_id)?;
+        state.serialize_field("season", &self.row.season)?;
+        state.serialize_field("day", &self.row.day)?;
+        state.serialize_field("day_special", &self.row.day_special)?;
+        state.serialize_field("player_name", &self.row.player_name)?;
+        state.serialize_field("team_id", &self.row.team_id)?;
+        state.serialize_field("opponent_id", &self.row.opponent_id)?;
+        state.serialize_field("opponent_name", &self.row.opponent_name)?;
+        state.serialize_field("home", &self.row.home)?;
+        state.serialize_field("team_score", &self.row.team_score())?;
+        state.serialize_field("opponent_score", &self.row.opponent_score())?;
+        state.serialize_field("result", &self.row.result())?;
+        for f in self.fields.iter() {
+            let name: &'static str = f.into();
+            state.serialize_field(name, &self.row.values[*f as usize])?;
+        }
+        state.end()
+    }
+}
+
+pub async fn gamelog(
+    State(ctx): State<AppState>,
+    Path(player_id): Path<String>,
+    Query(mut q): Query<GameLogRequest>,
+) -> Result<Response, AppError> {
+    let format = q.format.unwrap_or(StatsFormat::Csv);
+
+    if let Some(season) = q.season {
+        q.start = Some(SeasonDay::new(season, 0));
+        q.end = Some(SeasonDay::new(season + 1, 0));
+    }
+
+    if q.fields.is_empty() {
+        q.fields = StatKey::VARIANTS.to_vec();
+    }
+    let fields = Arc::new(q.fields);
+
+    let rows = ctx
+        .db
+        .get_game_log(GetGameLogQuery {
+            player: player_id,
+            start: q.start.map(Into::into),
+            end: q.end.map(Into::into),
+            fields: fields.to_vec(),
+        })
+        .await?;
+
+    if let Some(columnar) = format.columnar() {
+        let batch = gamelog_record_batch(&fields, &rows)?;
+        return Ok(columnar.into_response(batch).await?);
+    }
+
+    let rows = rows
+        .into_iter()
+        .map(|row| GameLogOutputRow {
+            row,
+            fields: fields.clone(),
+        })
+        .collect();
+
+    Ok(table_response(format, rows, || GameLogOutputRow {
+        row: GameLogRow {
+            game_id: String::new(),
+            season: 0,
+            day: 0,
+            day_special: None,
+            state: None,
+            team_id: String::new(),
+            player_name: None,
+            opponent_id: None,
+            opponent_name: None,
+            home: false,
+            home_score: None,
+            away_score: None,
+            values: [0; StatKey::COUNT],
+        },
+        fields,
+    })?)
+}
+
+// same columns in the same order as the GameLogOutputRow serializer
+fn gamelog_record_batch(
+    fields: &[StatKey],
+    rows: &[GameLogRow],
+) -> anyhow::Result<arrow::array::RecordBatch> {
+    let mut schema = vec![
+        Field::new("game_id", DataType::Utf8, false),
+        Field::new("season", DataType::Int16, false),
+        Field::new("day", DataType::Int16, false),
+        Field::new("day_special", DataType::Utf8, true),
+        Field::new("player_name", DataType::Utf8, true),
+        Field::new("team_id", DataType::Utf8, false),
+        Field::new("opponent_id", DataType::Utf8, true),
+        Field::new("opponent_name", DataType::Utf8, true),
+        Field::new("home", DataType::Boolean, false),
+        Field::new("team_score", DataType::Int32, true),
+        Field::new("opponent_score", DataType::Int32, true),
+        Field::new("result", DataType::Utf8, true),
+    ];
+    let mut columns: Vec<ArrayRef> = vec![
+        columnar::string_column(rows, |r| Some(r.game_id.as_str())),
+        Arc::new(Int16Array::from_iter_values(rows.iter().map(|r| r.season))),
+        Arc::new(Int16Array::from_iter_values(rows.iter().map(|r| r.day))),
+        columnar::string_column(rows, |r| r.day_special.as_deref()),
+        columnar::string_column(rows, |r| r.player_name.as_deref()),
+        columnar::string_column(rows, |r| Some(r.team_id.as_str())),
+        columnar::string_column(rows, |r| r.opponent_id.as_deref()),
+        columnar::string_column(rows, |r| r.opponent_name.as_deref()),
+        Arc::new(BooleanArray::from_iter(rows.iter().map(|r| Some(r.home)))),
+        Arc::new(Int32Array::from_iter(rows.iter().map(|r| r.team_score()))),
+        Arc::new(Int32Array::from_iter(
+            rows.iter().map(|r| r.opponent_score()),
+        )),
+        columnar::string_column(rows, |r| r.result()),
+    ];
+    for f in fields {
+        let name: &'static str = f.into();
+        schema.push(Field::new(name, DataType::UInt32, false));
+        columns.push(Arc::new(UInt32Array::from_iter_values(
+            rows.iter().map(|r| r.values[*f as usize]),
+        )));
+    }
+
+    columnar::record_batch(Schema::new(schema), columns)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PlayerPercentilesQuery {
+    pub season: i32,
+    pub min_pa: Option<i32>,
+    pub min_outs: Option<i32>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PlayerPercentilesResponse {
+    player_id: String,
+    season: i32,
+    // the league they've played the most in, if they moved around
+    league_id: Option<String>,
+    pa: i32,
+    outs: i32,
+    min_pa: i32,
+    min_outs: i32,
+    qualified_batting: bool,
+    qualified_pitching: bool,
+    stats: BTreeMap<&'static str, PlayerPercentile>,
+}
+
+type StatDistribution = fn(&LeagueAggregateLeague) -> &LeagueAggregateStat;
+
+// percentiles are flipped for stats where lower is better, so 100 is always the best
+#[derive(Serialize, Debug)]
+pub struct PlayerPercentile {
+    value: f32,
+    league: Option<f32>,
+    all: Option<f32>,
+}
+
+pub async fn percentiles(
+    State(ctx): State<AppState>,
+    Path(player_id): Path<String>,
+    Query(q): Query<PlayerPercentilesQuery>,
+) -> Result<Json<PlayerPercentilesResponse>, AppError> {
+    let query = GetPercentilesQuery {
+        season: q.season,
+        league: None,
+        start_day: None,
+        end_day: None,
+        min_pa: q.min_pa.unwrap_or(DEFAULT_PERCENTILE_MIN_PA),
+        min_outs: q.min_outs.unwrap_or(DEFAULT_PERCENTILE_MIN_OUTS),
+    };
+
+    let Some(rates) = ctx.db.get_player_rates(&player_id, &query).await? else {
+        return Err(anyhow::anyhow!("no stats for player in season").into());
+    };
+
+    let (min_pa, min_outs) = (query.min_pa, query.min_outs);
+    let aggregate = if q.min_pa.is_none() && q.min_outs.is_none() {
+        ctx.percentile_cache.get((q.season, None)).await?
+    } else {
+        Arc::new(get_league_aggregate(&ctx, query).await?)
+    };
+    let league = rates
+        .league_id
+        .as_ref()
+        .and_then(|id| aggregate.leagues.get(id));
+    let all = aggregate.leagues.get("all");
+
+    let fip_const = league
+        .or(all)
+        .and_then(|l| l.fip_const.median())
+        .unwrap_or(0.0);
+
+    let mut stats = BTreeMap::new();
+    // name, value, higher is better, distribution
+    let entries: [(&'static str, Option<f32>, bool, StatDistribution); 12] = [
+        ("ba", rates.ba, true, |l| &l.ba),
+        ("obp", rates.obp, true, |l| &l.obp),
+        ("slg", rates.slg, true, |l| &l.slg),
+        ("ops", rates.ops, true, |l| &l.ops),
+        ("sb_success", rates.sb_success, true, |l| &l.sb_success),
+        ("era", rates.era, false, |l| &l.era),
+        ("whip", rates.whip, false, |l| &l.whip),
+        ("fip", rates.fip_base, false, |l| &l.fip_base),
+        ("h9", rates.h9, false, |l| &l.h9),
+        ("k9", rates.k9, true, |l| &l.k9),
+        ("bb9", rates.bb9, false, |l| &l.bb9),
+        ("hr9", rates.hr9, false, |l| &l.hr9),
+    ];
+    for (name, value, higher_is_better, stat) in entries {
+        // under the thresholds
+        let Some(value) = value else {
+            continue;
+        };
+
+        let rank = |l: Option<&LeagueAggregateLeague>| {
+            let rank = stat(l?).rank(value)?;
+            Some(if higher_is_better { rank } else { 100.0 - rank })
+        };
+        stats.insert(
+            name,
+            PlayerPercentile {
+                value: if name == "fip" {
+                    value + fip_const
+                } else {
+                    value
+                },
+                league: rank(league),
+                all: rank(all),
+            },
+        );
+    }
+
+    Ok(Json(PlayerPercentilesResponse {
+        player_id,
+        season: q.season,
+        league_id: rates.league_id,
+        pa: rates.pa,
+        outs: rates.outs,
+        min_pa,
+        min_outs,
+        qualified_batting: rates.pa >= min_pa,
+        qualified_pitching: rates.outs >= min_outs,
+        stats,
+    }))
+}
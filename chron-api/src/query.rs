@@ -0,0 +1,369 @@
+use std::{collections::BTreeMap, path::Path, sync::Arc, time::Duration};
+
+use arrow::{
+    array::{ArrayRef, BooleanArray, Float64Array, Int64Array, StringArray},
+    datatypes::{DataType, Field, Schema},
+};
+use axum::{
+    extract::{Query, State},
+    http::{HeaderName, HeaderValue, header},
+    response::{IntoResponse, Response},
+};
+use duckdb::{
+    Config, Connection,
+    arrow::{
+        array::{Array, AsArray, RecordBatch as DuckBatch},
+        compute::cast,
+        datatypes::{DataType as DuckDataType, Float64Type, Int64Type},
+        util::display::{ArrayFormatter, FormatOptions},
+    },
+};
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{AppError, AppState, columnar, stats::StatsFormat};
+
+const DEFAULT_ROW_LIMIT: usize = 10_000;
+const MAX_ROW_LIMIT: usize = 100_000;
+const QUERY_TIMEOUT: Duration = Duration::from_secs(30);
+const QUERY_MEMORY_LIMIT: &str = "1GB";
+const QUERY_THREADS: usize = 2;
+// how many queries can run at once, they're all cpu/memory heavy
+pub const MAX_CONCURRENT_QUERIES: usize = 4;
+
+static TRUNCATED_HEADER: HeaderName = HeaderName::from_static("x-chron-truncated");
+
+#[derive(Deserialize, Debug)]
+pub struct QueryRequest {
+    pub sql: String,
+    pub format: Option<StatsFormat>,
+    pub limit: Option<usize>,
+}
+
+// just the bits of the export manifest we care about
+#[derive(Deserialize)]
+struct ExportManifest {
+    tables: BTreeMap<String, Vec<ExportFile>>,
+}
+
+#[derive(Deserialize)]
+struct ExportFile {
+    path: String,
+}
+
+enum QueryColumn {
+    Bool(Vec<Option<bool>>),
+    Int(Vec<Option<i64>>),
+    Float(Vec<Option<f64>>),
+    // anything else (dates, lists, structs...) gets stringified
+    Text(Vec<Option<String>>),
+}
+
+impl QueryColumn {
+    fn json_value(&self, i: usize) -> serde_json::Value {
+        match self {
+            QueryColumn::Bool(v) => v[i].into(),
+            QueryColumn::Int(v) => v[i].into(),
+            QueryColumn::Float(v) => v[i].into(),
+            QueryColumn::Text(v) => v[i].clone().into(),
+        }
+    }
+
+    fn csv_value(&self, i: usize) -> String {
+        match self {
+            QueryColumn::Bool(v) => v[i].map(|x| x.to_string()),
+            QueryColumn::Int(v) => v[i].map(|x| x.to_string()),
+            QueryColumn::Float(v) => v[i].map(|x| x.to_string()),
+            QueryColumn::Text(v) => v[i].clone(),
+        }
+        .unwrap_or_default()
+    }
+
+    fn data_type(&self) -> DataType {
+        match self {
+            QueryColumn::Bool(_) => DataType::Boolean,
+            QueryColumn::Int(_) => DataType::Int64,
+            QueryColumn::Float(_) => DataType::Float64,
+            QueryColumn::Text(_) => DataType::Utf8,
+        }
+    }
+
+    fn to_arrow(&self) -> ArrayRef {
+        match self {
+            QueryColumn::Bool(v) => Arc::new(BooleanArray::from(v.clone())),
+            QueryColumn::Int(v) => Arc::new(Int64Array::from(v.clone())),
+            QueryColumn::Float(v) => Arc::new(Float64Array::from(v.clone())),
+            QueryColumn::Text(v) => Arc::new(StringArray::from(v.clone())),
+        }
+    }
+}
+
+struct QueryResult {
+    names: Vec<String>,
+    columns: Vec<QueryColumn>,
+    rows: usize,
+    truncated: bool,
+}
+
+// read-only sql over the parquet exports, so nobody gets to hammer postgres directly
+pub async fn query(
+    State(ctx): State<AppState>,
+    Query(q): Query<QueryRequest>,
+) -> Result<Response, AppError> {
+    Ok(run_sandboxed(ctx, q).await?)
+}
+
+async fn run_sandboxed(ctx: AppState, q: QueryRequest) -> anyhow::Result<Response> {
+    let Some(export_path) = ctx.config.export_path.clone() else {
+        return Err(anyhow::anyhow!("no export path configured"));
+    };
+    let format = q.format.unwrap_or(StatsFormat::Csv);
+    let limit = q.limit.unwrap_or(DEFAULT_ROW_LIMIT).clamp(1, MAX_ROW_LIMIT);
+
+    let _permit = ctx.query_semaphore.acquire().await?;
+
+    let (handle_tx, handle_rx) = tokio::sync::oneshot::channel();
+    let sql = q.sql;
+    let task = tokio::task::spawn_blocking(move || -> anyhow::Result<QueryResult> {
+        let conn = open_sandbox(Path::new(&export_path))?;
+        let _ = handle_tx.send(conn.interrupt_handle());
+        run_query(&conn, &sql, limit)
+    });
+
+    let result = match tokio::time::timeout(QUERY_TIMEOUT, task).await {
+        Ok(res) => res??,
+        Err(_) => {
+            // the query keeps the blocking thread busy until duckdb notices the interrupt
+            if let Ok(handle) = handle_rx.await {
+                handle.interrupt();
+            }
+            return Err(anyhow::anyhow!(
+                "query took longer than {}s",
+                QUERY_TIMEOUT.as_secs()
+            ));
+        }
+    };
+    info!(
+        "query returned {} rows (truncated: {})",
+        result.rows, result.truncated
+    );
+
+    let truncated = HeaderValue::from_static(if result.truncated { "true" } else { "false" });
+    let mut response = match format {
+        StatsFormat::Csv => csv_response(&result)?,
+        StatsFormat::Json => {
+            let body = serde_json::json!({
+                "columns": result.names,
+                "rows": (0..result.rows)
+                    .map(|i| result.columns.iter().map(|c| c.json_value(i)).collect())
+                    .collect::<Vec<Vec<_>>>(),
+                "truncated": result.truncated,
+            });
+            axum::Json(body).into_response()
+        }
+        StatsFormat::Ndjson => {
+            let mut body = String::new();
+            for i in 0..result.rows {
+                let row = serde_json::Map::from_iter(
+                    result
+                        .names
+                        .iter()
+                        .zip(&result.columns)
+                        .map(|(name, c)| (name.clone(), c.json_value(i))),
+                );
+                body.push_str(&serde_json::to_string(&row)?);
+                body.push('\n');
+            }
+            (
+                [(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/x-ndjson; charset=utf-8"),
+                )],
+                body,
+            )
+                .into_response()
+        }
+        StatsFormat::Arrow | StatsFormat::Parquet => {
+            let schema = Schema::new(
+                result
+                    .names
+                    .iter()
+                    .zip(&result.columns)
+                    .map(|(name, c)| Field::new(name, c.data_type(), true))
+                    .collect::<Vec<_>>(),
+            );
+            let batch = columnar::record_batch(
+                schema,
+                result.columns.iter().map(|c| c.to_arrow()).collect(),
+            )?;
+            let Some(columnar) = format.columnar() else {
+                unreachable!()
+            };
+            columnar.into_response(batch).await?
+        }
+    };
+    response
+        .headers_mut()
+        .insert(TRUNCATED_HEADER.clone(), truncated);
+    Ok(response)
+}
+
+fn csv_response(result: &QueryResult) -> anyhow::Result<Response> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(&result.names)?;
+    for i in 0..result.rows {
+        writer.write_record(result.columns.iter().map(|c| c.csv_value(i)))?;
+    }
+
+    Ok((
+        // same as /stats, wrong on purpose so it shows up in the browser
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; charset=utf-8"),
+        )],
+        writer.into_inner()?,
+    )
+        .into_response())
+}
+
+// fresh in-memory db per query with a view for every exported table,
+// then lock it down so the query itself can't touch anything else
+fn open_sandbox(export_path: &Path) -> anyhow::Result<Connection> {
+    let root = std::fs::canonicalize(export_path)?;
+    let manifest: ExportManifest =
+        serde_json::from_slice(&std::fs::read(root.join("manifest.json"))?)?;
+
+    let config = Config::default()
+        .max_memory(QUERY_MEMORY_LIMIT)?
+        .threads(QUERY_THREADS as i64)?;
+    let conn = Connection::open_in_memory_with_flags(config)?;
+
+    let mut setup = String::new();
+    for (table, files) in &manifest.tables {
+        if files.is_empty() || !table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            continue;
+        }
+
+        let paths = files
+            .iter()
+            .map(|f| sql_string(&root.join(&f.path)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        // the partition columns are already in the files, don't want them twice
+        setup.push_str(&format!(
+            "create view \"{table}\" as select * from read_parquet([{paths}], hive_partitioning = false);\n"
+        ));
+    }
+
+    setup.push_str(&format!(
+        "set allowed_directories = [{}];\n",
+        sql_string(&root)
+    ));
+    setup.push_str("set enable_external_access = false;\n");
+    setup.push_str("set autoinstall_known_extensions = false;\n");
+    setup.push_str("set autoload_known_extensions = false;\n");
+    setup.push_str("set lock_configuration = true;\n");
+    conn.execute_batch(&setup)?;
+
+    Ok(conn)
+}
+
+fn sql_string(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', "''"))
+}
+
+fn run_query(conn: &Connection, sql: &str, limit: usize) -> anyhow::Result<QueryResult> {
+    let sql = sql.trim().trim_end_matches(';');
+
+    // wrapping it in a subquery means only a single read-only statement will even parse,
+    // no copy/attach/pragma/set/multiple statements etc
+    let wrapped = format!("select * from ({sql}\n) limit {}", limit + 1);
+    let mut stmt = conn.prepare(&wrapped)?;
+    let batches: Vec<DuckBatch> = stmt.query_arrow([])?.collect();
+    let names = stmt.column_names();
+
+    let total: usize = batches.iter().map(|b| b.num_rows()).sum();
+    let rows = total.min(limit);
+
+    let mut columns = Vec::with_capacity(names.len());
+    for col in 0..names.len() {
+        let arrays = batches
+            .iter()
+            .map(|b| b.column(col).clone())
+            .collect::<Vec<_>>();
+        columns.push(convert_column(&arrays, rows)?);
+    }
+
+    Ok(QueryResult {
+        names,
+        columns,
+        rows,
+        truncated: total > limit,
+    })
+}
+
+// duckdb has its own (newer) arrow, so everything gets flattened into a few plain types
+fn convert_column(arrays: &[Arc<dyn Array>], rows: usize) -> anyhow::Result<QueryColumn> {
+    let data_type = arrays
+        .first()
+        .map(|a| a.data_type().clone())
+        .unwrap_or(DuckDataType::Null);
+
+    let column = match data_type {
+        DuckDataType::Boolean => QueryColumn::Bool(
+            arrays
+                .iter()
+                .flat_map(|a| a.as_boolean().iter().collect::<Vec<_>>())
+                .take(rows)
+                .collect(),
+        ),
+        DuckDataType::Int8
+        | DuckDataType::Int16
+        | DuckDataType::Int32
+        | DuckDataType::Int64
+        | DuckDataType::UInt8
+        | DuckDataType::UInt16
+        | DuckDataType::UInt32
+        | DuckDataType::UInt64 => {
+            let mut values = Vec::with_capacity(rows);
+            for a in arrays {
+                let a = cast(a, &DuckDataType::Int64)?;
+                values.extend(a.as_primitive::<Int64Type>().iter());
+            }
+            values.truncate(rows);
+            QueryColumn::Int(values)
+        }
+        DuckDataType::Float16
+        | DuckDataType::Float32
+        | DuckDataType::Float64
+        | DuckDataType::Decimal128(_, _)
+        | DuckDataType::Decimal256(_, _) => {
+            let mut values = Vec::with_capacity(rows);
+            for a in arrays {
+                let a = cast(a, &DuckDataType::Float64)?;
+                values.extend(a.as_primitive::<Float64Type>().iter());
+            }
+            values.truncate(rows);
+            QueryColumn::Float(values)
+        }
+        _ => {
+            let mut values = Vec::with_capacity(rows);
+            for a in arrays {
+                let formatter = ArrayFormatter::try_new(a.as_ref(), &FormatOptions::default())?;
+                for i in 0..a.len() {
+                    if values.len() >= rows {
+                        break;
+                    }
+                    values.push(if a.is_null(i) {
+                        None
+                    } else {
+                        Some(formatter.value(i).to_string())
+                    });
+                }
+            }
+            QueryColumn::Text(values)
+        }
+    };
+
+    Ok(column)
+}
@@ -0,0 +1,74 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use chron_db::derived::{Re24MatrixRow, Re24PlayerRow};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppError, AppState};
+
+#[derive(Deserialize, Debug)]
+pub struct Re24Query {
+    pub season: i32,
+    #[serde(default)]
+    pub count: Option<i64>,
+    #[serde(default)]
+    pub min_pa: Option<i32>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Re24Response {
+    season: i32,
+    matrix: Vec<Re24MatrixEntry>,
+    batters: Vec<Re24PlayerRow>,
+    pitchers: Vec<Re24PlayerRow>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Re24MatrixEntry {
+    outs: i32,
+    // eg. "1_3" for runners on first and third
+    bases: String,
+    occurrences: i32,
+    run_expectancy: f32,
+}
+
+impl From<Re24MatrixRow> for Re24MatrixEntry {
+    fn from(row: Re24MatrixRow) -> Self {
+        let bases = [(1, '1'), (2, '2'), (4, '3')]
+            .iter()
+            .map(|(bit, c)| if row.bases & bit != 0 { *c } else { '_' })
+            .collect();
+        Re24MatrixEntry {
+            outs: row.outs,
+            bases,
+            occurrences: row.occurrences,
+            run_expectancy: row.run_expectancy,
+        }
+    }
+}
+
+pub async fn re24(
+    State(ctx): State<AppState>,
+    Query(q): Query<Re24Query>,
+) -> Result<Json<Re24Response>, AppError> {
+    let count = q.count.unwrap_or(50);
+    let min_pa = q.min_pa.unwrap_or(1);
+
+    let matrix = ctx.db.get_re24_matrix(q.season).await?;
+    let batters = ctx
+        .db
+        .get_re24_leaders(q.season, false, min_pa, count)
+        .await?;
+    let pitchers = ctx
+        .db
+        .get_re24_leaders(q.season, true, min_pa, count)
+        .await?;
+
+    Ok(Json(Re24Response {
+        season: q.season,
+        matrix: matrix.into_iter().map(Into::into).collect(),
+        batters,
+        pitchers,
+    }))
+}
@@ -0,0 +1,245 @@
+use std::collections::{BTreeMap, HashMap};
+
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use chron_db::derived::{DbGameResult, GetGameResultsQuery};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppError, AppState};
+
+// the usual baseball-reference exponent
+const PYTHAG_EXPONENT: f64 = 1.83;
+
+#[derive(Deserialize, Debug)]
+pub struct StandingsQuery {
+    pub season: i32,
+    // standings as they were at the end of this day
+    pub day: Option<i32>,
+    pub league: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct StandingsResponse {
+    season: i32,
+    day: Option<i32>,
+    leagues: Vec<LeagueStandings>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LeagueStandings {
+    league_id: Option<String>,
+    name: Option<String>,
+    teams: Vec<TeamStanding>,
+}
+
+#[derive(Serialize, Debug, Default, Clone, Copy)]
+pub struct Record {
+    pub wins: u32,
+    pub losses: u32,
+}
+
+impl Record {
+    pub fn add(&mut self, won: bool) {
+        if won {
+            self.wins += 1;
+        } else {
+            self.losses += 1;
+        }
+    }
+
+    pub fn pct(&self) -> f64 {
+        let games = self.wins + self.losses;
+        if games == 0 {
+            0.0
+        } else {
+            self.wins as f64 / games as f64
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct TeamStanding {
+    team_id: String,
+    name: Option<String>,
+    wins: u32,
+    losses: u32,
+    win_pct: f64,
+    games_back: f64,
+    runs_scored: i32,
+    runs_allowed: i32,
+    run_differential: i32,
+    // eg. "W3" or "L1"
+    streak: Option<String>,
+    home: Record,
+    away: Record,
+    last_10: Record,
+    pythag_pct: f64,
+    pythag_wins: f64,
+    pythag_losses: f64,
+}
+
+#[derive(Default)]
+pub struct TeamAccumulator {
+    pub overall: Record,
+    pub home: Record,
+    pub away: Record,
+    pub runs_scored: i32,
+    pub runs_allowed: i32,
+    // most recent last
+    results: Vec<bool>,
+}
+
+impl TeamAccumulator {
+    pub fn add_game(&mut self, home: bool, ours: i32, theirs: i32) {
+        self.runs_scored += ours;
+        self.runs_allowed += theirs;
+
+        // ties shouldn't happen, but don't count them as anything if they do
+        if ours == theirs {
+            return;
+        }
+
+        let won = ours > theirs;
+        self.overall.add(won);
+        if home {
+            self.home.add(won);
+        } else {
+            self.away.add(won);
+        }
+        self.results.push(won);
+    }
+
+    fn streak(&self) -> Option<String> {
+        let last = *self.results.last()?;
+        let len = self
+            .results
+            .iter()
+            .rev()
+            .take_while(|x| **x == last)
+            .count();
+        Some(format!("{}{}", if last { "W" } else { "L" }, len))
+    }
+
+    fn last_10(&self) -> Record {
+        let mut record = Record::default();
+        for won in self.results.iter().rev().take(10) {
+            record.add(*won);
+        }
+        record
+    }
+
+    fn pythag_pct(&self) -> f64 {
+        let rs = (self.runs_scored as f64).powf(PYTHAG_EXPONENT);
+        let ra = (self.runs_allowed as f64).powf(PYTHAG_EXPONENT);
+        if rs + ra == 0.0 { 0.0 } else { rs / (rs + ra) }
+    }
+}
+
+pub async fn standings(
+    State(ctx): State<AppState>,
+    Query(q): Query<StandingsQuery>,
+) -> Result<Json<StandingsResponse>, AppError> {
+    let games = ctx
+        .db
+        .get_game_results(GetGameResultsQuery {
+            season: Some(q.season),
+            through_day: q.day,
+            // standings are regular season only
+            regular_season_only: true,
+            ..Default::default()
+        })
+        .await?;
+    let teams = ctx.db.get_teams().await?;
+    let leagues = ctx.db.get_leagues().await?;
+
+    let team_info: HashMap<_, _> = teams.iter().map(|t| (t.team_id.as_str(), t)).collect();
+    let league_names: HashMap<_, _> = leagues
+        .iter()
+        .map(|l| (l.league_id.as_str(), l.name.as_str()))
+        .collect();
+
+    let records = accumulate_records(&games);
+
+    // league -> teams
+    let mut by_league = BTreeMap::<Option<String>, Vec<TeamStanding>>::new();
+    for (team_id, acc) in records {
+        let team = team_info.get(team_id);
+        let league_id = team.and_then(|t| t.league_id.clone());
+        if q.league.is_some() && league_id != q.league {
+            continue;
+        }
+
+        let games_played = acc.overall.wins + acc.overall.losses;
+        let pythag_pct = acc.pythag_pct();
+        by_league.entry(league_id).or_default().push(TeamStanding {
+            team_id: team_id.to_string(),
+            name: team.map(|t| format!("{} {}", t.location, t.name)),
+            wins: acc.overall.wins,
+            losses: acc.overall.losses,
+            win_pct: acc.overall.pct(),
+            games_back: 0.0,
+            runs_scored: acc.runs_scored,
+            runs_allowed: acc.runs_allowed,
+            run_differential: acc.runs_scored - acc.runs_allowed,
+            streak: acc.streak(),
+            home: acc.home,
+            away: acc.away,
+            last_10: acc.last_10(),
+            pythag_pct,
+            pythag_wins: pythag_pct * games_played as f64,
+            pythag_losses: (1.0 - pythag_pct) * games_played as f64,
+        });
+    }
+
+    let mut leagues = Vec::new();
+    for (league_id, mut teams) in by_league {
+        teams.sort_by(|a, b| {
+            b.win_pct
+                .total_cmp(&a.win_pct)
+                .then(b.run_differential.cmp(&a.run_differential))
+                .then(a.team_id.cmp(&b.team_id))
+        });
+
+        if let Some((leader_wins, leader_losses)) = teams.first().map(|t| (t.wins, t.losses)) {
+            for team in teams.iter_mut() {
+                team.games_back = ((leader_wins as f64 - team.wins as f64)
+                    + (team.losses as f64 - leader_losses as f64))
+                    / 2.0;
+            }
+        }
+
+        leagues.push(LeagueStandings {
+            name: league_id
+                .as_deref()
+                .and_then(|id| league_names.get(id))
+                .map(|name| name.to_string()),
+            league_id,
+            teams,
+        });
+    }
+
+    Ok(Json(StandingsResponse {
+        season: q.season,
+        day: q.day,
+        leagues,
+    }))
+}
+
+pub fn accumulate_records(games: &[DbGameResult]) -> BTreeMap<&str, TeamAccumulator> {
+    let mut records = BTreeMap::<&str, TeamAccumulator>::new();
+    for game in games {
+        records.entry(&game.home_team_id).or_default().add_game(
+            true,
+            game.home_score,
+            game.away_score,
+        );
+        records.entry(&game.away_team_id).or_default().add_game(
+            false,
+            game.away_score,
+            game.home_score,
+        );
+    }
+    records
+}
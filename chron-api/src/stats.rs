@@ -4,24 +4,32 @@ use std::{
     sync::Arc,
 };
 
+use arrow::{
+    array::{ArrayRef, Int16Array, UInt32Array},
+    datatypes::{DataType, Field, Schema},
+};
 use axum::{
     body::Bytes,
     extract::State,
     http::{self, HeaderMap, HeaderValue},
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
 use axum_streams::{
     CsvStreamFormat, JsonArrayStreamFormat, JsonNewLineStreamFormat, StreamBodyAs,
     StreamBodyAsOptions, StreamingFormat,
 };
 use chron_base::StatKey;
-use chron_db::derived::{StatFilter, StatsQueryNew, StatsRow};
+use chron_db::derived::{StatFilter, StatsAccumulate, StatsQueryNew, StatsRow};
 use futures::{StreamExt, TryStreamExt, stream};
 use serde::{Deserialize, Serialize, Serializer, ser::SerializeStruct};
 use serde_qs::axum::QsQuery;
 use strum::EnumCount;
 
-use crate::{AppError, AppState, derived_api::SeasonDay};
+use crate::{
+    AppError, AppState,
+    columnar::{self, ColumnarFormat},
+    derived_api::SeasonDay,
+};
 
 use crate::chron_api::comma_separated2;
 
@@ -49,6 +57,18 @@ pub enum StatsFormat {
     Csv,
     Json,
     Ndjson,
+    Arrow,
+    Parquet,
+}
+
+impl StatsFormat {
+    pub fn columnar(&self) -> Option<ColumnarFormat> {
+        match self {
+            StatsFormat::Arrow => Some(ColumnarFormat::ArrowIpc),
+            StatsFormat::Parquet => Some(ColumnarFormat::Parquet),
+            _ => None,
+        }
+    }
 }
 
 // need custom serde bullshit because of the "variable" amount of fields
@@ -153,12 +173,18 @@ pub struct StatsRequest {
 
     #[serde(default)]
     pub names: bool,
+
+    // rolling totals over the last n days (or games, if grouping by game), reset at the start of each season
+    pub window: Option<u32>,
+    // running season-to-date totals, reset at the start of each season
+    #[serde(default)]
+    pub cumulative: bool,
 }
 
 pub async fn stats(
     State(ctx): State<AppState>,
     QsQuery(mut q): QsQuery<StatsRequest>,
-) -> Result<impl IntoResponse, AppError> {
+) -> Result<Response, AppError> {
     dbg!(&q);
     let format = q.format.unwrap_or(StatsFormat::Csv);
 
@@ -166,6 +192,21 @@ pub async fn stats(
 
     dedup_preserving_order(&mut q.fields);
 
+    let accumulate = match (q.window, q.cumulative) {
+        (Some(_), true) => {
+            return Err(anyhow::anyhow!("can't use both window and cumulative").into());
+        }
+        (Some(0), _) => return Err(anyhow::anyhow!("window must be at least 1").into()),
+        (Some(n), false) => Some(StatsAccumulate::Rolling(n)),
+        (None, true) => Some(StatsAccumulate::Cumulative),
+        (None, false) => None,
+    };
+
+    // running totals are always per-day, so make sure that ends up in the output
+    if accumulate.is_some() && !q.group.contains(&GroupColumn::Day) {
+        q.group.push(GroupColumn::Day);
+    }
+
     if let Some(season) = q.season {
         q.start = Some(SeasonDay::new(season, 0));
         q.end = Some(SeasonDay::new(season + 1, 0));
@@ -196,43 +237,61 @@ pub async fn stats(
         fields: q.fields.clone(),
         include_names: q.names,
         filters: q.filter.iter().map(|(k, v)| (*k, v.clone())).collect(),
+        accumulate,
     };
 
     let db = ctx.db.clone();
 
     let stream = db.get_stats(qq.clone())?;
-    let results = stream
-        .map_ok(|row| StatOutputRow { row, q: q.clone() })
-        .try_collect::<Vec<_>>()
-        .await?;
-    let is_empty = results.is_empty();
+    let rows = stream.try_collect::<Vec<_>>().await?;
+
+    if let Some(columnar) = format.columnar() {
+        let batch = stats_record_batch(&q, &rows)?;
+        return Ok(columnar.into_response(batch).await?);
+    }
+
+    let results = rows
+        .into_iter()
+        .map(|row| StatOutputRow { row, q: q.clone() })
+        .collect::<Vec<_>>();
+    Ok(table_response(format, results, || StatOutputRow {
+        row: StatsRow {
+            player: None,
+            player_name: None,
+            game: None,
+            team: None,
+            team_name: None,
+            league: None,
+            season: None,
+            day: None,
+            slot: None,
+            values: [0; StatKey::COUNT],
+        },
+        q,
+    })?)
+}
 
-    let s = stream::iter(results).map(|x| -> Result<StatOutputRow, axum::Error> { Ok(x) });
+// csv/json/ndjson output for any kind of row, callers handle arrow/parquet through columnar first
+// `null_row` only gets used to fake a csv header row when there's no data
+pub fn table_response<T: Serialize + Send + Sync + 'static>(
+    format: StatsFormat,
+    rows: Vec<T>,
+    null_row: impl FnOnce() -> T,
+) -> anyhow::Result<Response> {
+    let is_empty = rows.is_empty();
+
+    let s = stream::iter(rows).map(|x| -> Result<T, axum::Error> { Ok(x) });
     let opts = StreamBodyAsOptions::new().buffering_ready_items(1000);
 
     // if we're outputting csv and there are no rows, we still want to output a header row
     // so, we fake it a little bit...
     if format == StatsFormat::Csv && is_empty {
-        let null_row = StatOutputRow {
-            row: StatsRow {
-                player: None,
-                player_name: None,
-                game: None,
-                team: None,
-                team_name: None,
-                league: None,
-                season: None,
-                day: None,
-                slot: None,
-                values: [0; StatKey::COUNT],
-            },
-            q: q,
-        };
         return Ok(StreamBodyAs::with_options(
-            HeaderOnlyStreamFormat::new(CsvStreamFormat::new(true, b','), null_row),
+            HeaderOnlyStreamFormat::new(CsvStreamFormat::new(true, b','), null_row()),
             s,
             opts.content_type(HeaderValue::from_static("text/plain; charset=utf-8")),
-        ));
+        )
+        .into_response());
     }
 
     Ok(match format {
@@ -257,7 +316,69 @@ pub async fn stats(
                 "application/x-ndjson; charset=utf-8",
             )),
         ),
-    })
+        StatsFormat::Arrow | StatsFormat::Parquet => {
+            return Err(anyhow::anyhow!("{:?} output isn't supported here", format));
+        }
+    }
+    .into_response())
+}
+
+// same columns in the same order as the StatOutputRow serializer, but typed
+fn stats_record_batch(
+    q: &StatsRequest,
+    rows: &[StatsRow],
+) -> anyhow::Result<arrow::array::RecordBatch> {
+    let mut fields = Vec::new();
+    let mut columns: Vec<ArrayRef> = Vec::new();
+
+    let has_day = q.group.contains(&GroupColumn::Day);
+    if has_day || q.group.contains(&GroupColumn::Season) {
+        fields.push(Field::new("season", DataType::Int16, false));
+        columns.push(Arc::new(Int16Array::from_iter_values(
+            rows.iter().map(|r| r.season.unwrap_or(0)),
+        )));
+    }
+    if has_day {
+        fields.push(Field::new("day", DataType::Int16, false));
+        columns.push(Arc::new(Int16Array::from_iter_values(
+            rows.iter().map(|r| r.day.unwrap_or(0)),
+        )));
+    }
+    if q.group.contains(&GroupColumn::Game) {
+        fields.push(Field::new("game_id", DataType::Utf8, true));
+        columns.push(columnar::string_column(rows, |r| r.game.as_deref()));
+    }
+    if q.group.contains(&GroupColumn::Player) {
+        fields.push(Field::new("player_id", DataType::Utf8, true));
+        columns.push(columnar::string_column(rows, |r| r.player.as_deref()));
+    }
+    if (q.group.contains(&GroupColumn::Player) && q.names)
+        || q.group.contains(&GroupColumn::PlayerName)
+    {
+        fields.push(Field::new("player_name", DataType::Utf8, true));
+        columns.push(columnar::string_column(rows, |r| r.player_name.as_deref()));
+    }
+    if q.group.contains(&GroupColumn::Team) {
+        fields.push(Field::new("team_id", DataType::Utf8, true));
+        columns.push(columnar::string_column(rows, |r| r.team.as_deref()));
+        if q.names {
+            fields.push(Field::new("team_name", DataType::Utf8, true));
+            columns.push(columnar::string_column(rows, |r| r.team_name.as_deref()));
+        }
+    }
+    if q.group.contains(&GroupColumn::League) {
+        fields.push(Field::new("league_id", DataType::Utf8, true));
+        columns.push(columnar::string_column(rows, |r| r.league.as_deref()));
+    }
+    for f in &q.fields {
+        let name: &'static str = f.into();
+        fields.push(Field::new(name, DataType::UInt32, false));
+        columns.push(Arc::new(UInt32Array::from_iter_values(
+            rows.iter().map(|r| r.values[*f as usize]),
+        )));
+    }
+
+    columnar::record_batch(Schema::new(fields), columns)
 }
 
 // way more generic than it needs to be
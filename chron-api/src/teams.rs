@@ -0,0 +1,299 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use chron_db::{
+    derived::{DbGame, DbGameResult, GetGameResultsQuery, GetGamesQuery},
+    queries::SortOrder,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AppError, AppState,
+    standings::{Record, TeamAccumulator, accumulate_records},
+};
+
+#[derive(Serialize, Debug)]
+pub struct TeamGame {
+    game_id: String,
+    season: i32,
+    day: i32,
+    day_special: Option<String>,
+    state: String,
+    opponent_id: String,
+    home: bool,
+    team_score: Option<i32>,
+    opponent_score: Option<i32>,
+    result: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    opponent_win_pct: Option<f64>,
+}
+
+impl TeamGame {
+    fn from_result(team_id: &str, game: &DbGameResult) -> TeamGame {
+        let home = game.home_team_id == team_id;
+        let (ours, theirs) = if home {
+            (game.home_score, game.away_score)
+        } else {
+            (game.away_score, game.home_score)
+        };
+
+        TeamGame {
+            game_id: game.game_id.clone(),
+            season: game.season,
+            day: game.day,
+            day_special: game.day_special.clone(),
+            state: "Complete".to_string(),
+            opponent_id: if home {
+                game.away_team_id.clone()
+            } else {
+                game.home_team_id.clone()
+            },
+            home,
+            team_score: Some(ours),
+            opponent_score: Some(theirs),
+            result: game_result(ours, theirs),
+            opponent_win_pct: None,
+        }
+    }
+
+    fn from_game(team_id: &str, game: DbGame) -> TeamGame {
+        let home = game.home_team_id == team_id;
+        let score = |key: &str| {
+            game.last_update
+                .as_ref()
+                .and_then(|x| x.get(key))
+                .and_then(|x| x.as_i64())
+                .map(|x| x as i32)
+        };
+        let (home_score, away_score) = (score("home_score"), score("away_score"));
+        let (ours, theirs) = if home {
+            (home_score, away_score)
+        } else {
+            (away_score, home_score)
+        };
+
+        let result = match (ours, theirs) {
+            (Some(ours), Some(theirs)) if game.state == "Complete" => game_result(ours, theirs),
+            _ => None,
+        };
+
+        TeamGame {
+            opponent_id: if home {
+                game.away_team_id
+            } else {
+                game.home_team_id
+            },
+            game_id: game.game_id,
+            season: game.season,
+            day: game.day,
+            day_special: game.day_special,
+            state: game.state,
+            home,
+            team_score: ours,
+            opponent_score: theirs,
+            result,
+            opponent_win_pct: None,
+        }
+    }
+}
+
+fn game_result(ours: i32, theirs: i32) -> Option<&'static str> {
+    Some(match ours.cmp(&theirs) {
+        std::cmp::Ordering::Greater => "W",
+        std::cmp::Ordering::Less => "L",
+        std::cmp::Ordering::Equal => "T",
+    })
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct RecordSummary {
+    wins: u32,
+    losses: u32,
+    runs_scored: i32,
+    runs_allowed: i32,
+    home: Record,
+    away: Record,
+}
+
+impl From<&TeamAccumulator> for RecordSummary {
+    fn from(acc: &TeamAccumulator) -> Self {
+        RecordSummary {
+            wins: acc.overall.wins,
+            losses: acc.overall.losses,
+            runs_scored: acc.runs_scored,
+            runs_allowed: acc.runs_allowed,
+            home: acc.home,
+            away: acc.away,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct HeadToHeadQuery {
+    // all seasons if not given
+    pub season: Option<i32>,
+    // postseason/special day games count unless this is set
+    #[serde(default)]
+    pub regular_season_only: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct HeadToHeadResponse {
+    team_id: String,
+    opponent_id: String,
+    #[serde(flatten)]
+    record: RecordSummary,
+    seasons: BTreeMap<i32, RecordSummary>,
+    games: Vec<TeamGame>,
+}
+
+pub async fn head_to_head(
+    State(ctx): State<AppState>,
+    Path((team_id, opponent_id)): Path<(String, String)>,
+    Query(q): Query<HeadToHeadQuery>,
+) -> Result<Json<HeadToHeadResponse>, AppError> {
+    if team_id == opponent_id {
+        return Err(anyhow::anyhow!("a team can't play itself").into());
+    }
+
+    let games = ctx
+        .db
+        .get_game_results(GetGameResultsQuery {
+            season: q.season,
+            team: Some(team_id.clone()),
+            opponent: Some(opponent_id.clone()),
+            regular_season_only: q.regular_season_only,
+            ..Default::default()
+        })
+        .await?;
+
+    let mut overall = TeamAccumulator::default();
+    let mut seasons = BTreeMap::<i32, TeamAccumulator>::new();
+    for game in &games {
+        let home = game.home_team_id == team_id;
+        let (ours, theirs) = if home {
+            (game.home_score, game.away_score)
+        } else {
+            (game.away_score, game.home_score)
+        };
+        overall.add_game(home, ours, theirs);
+        seasons
+            .entry(game.season)
+            .or_default()
+            .add_game(home, ours, theirs);
+    }
+
+    Ok(Json(HeadToHeadResponse {
+        record: (&overall).into(),
+        seasons: seasons.iter().map(|(s, acc)| (*s, acc.into())).collect(),
+        games: games
+            .iter()
+            .map(|g| TeamGame::from_result(&team_id, g))
+            .collect(),
+        team_id,
+        opponent_id,
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ScheduleQuery {
+    pub season: i32,
+    // applies to the game list, the team's record and the opponent records alike
+    #[serde(default)]
+    pub regular_season_only: bool,
+}
+
+// average season win% of the opponents faced (or still to face), one entry per game
+#[derive(Serialize, Debug)]
+pub struct StrengthOfSchedule {
+    played: Option<f64>,
+    remaining: Option<f64>,
+    overall: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ScheduleResponse {
+    team_id: String,
+    season: i32,
+    #[serde(flatten)]
+    record: RecordSummary,
+    strength_of_schedule: StrengthOfSchedule,
+    games: Vec<TeamGame>,
+}
+
+pub async fn schedule(
+    State(ctx): State<AppState>,
+    Path(team_id): Path<String>,
+    Query(q): Query<ScheduleQuery>,
+) -> Result<Json<ScheduleResponse>, AppError> {
+    let games = ctx
+        .db
+        .get_games(GetGamesQuery {
+            season: Some(q.season),
+            day: None,
+            team: Some(team_id.clone()),
+            order: SortOrder::Asc,
+            count: 1000,
+            page: None,
+        })
+        .await?;
+
+    let results = ctx
+        .db
+        .get_game_results(GetGameResultsQuery {
+            season: Some(q.season),
+            regular_season_only: q.regular_season_only,
+            ..Default::default()
+        })
+        .await?;
+    let records = accumulate_records(&results);
+
+    let mut games: Vec<TeamGame> = games
+        .items
+        .into_iter()
+        .filter(|g| !q.regular_season_only || g.day_special.is_none())
+        .map(|g| TeamGame::from_game(&team_id, g))
+        .collect();
+
+    let (mut played, mut remaining) = (Vec::new(), Vec::new());
+    for game in games.iter_mut() {
+        game.opponent_win_pct = records
+            .get(game.opponent_id.as_str())
+            .map(|acc| acc.overall.pct());
+
+        if let Some(pct) = game.opponent_win_pct {
+            if game.state == "Complete" {
+                played.push(pct);
+            } else {
+                remaining.push(pct);
+            }
+        }
+    }
+
+    let overall: Vec<f64> = played.iter().chain(remaining.iter()).copied().collect();
+    Ok(Json(ScheduleResponse {
+        record: records
+            .get(team_id.as_str())
+            .map(Into::into)
+            .unwrap_or_default(),
+        strength_of_schedule: StrengthOfSchedule {
+            played: mean(&played),
+            remaining: mean(&remaining),
+            overall: mean(&overall),
+        },
+        team_id,
+        season: q.season,
+        games,
+    }))
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.iter().sum::<f64>() / values.len() as f64)
+    }
+}
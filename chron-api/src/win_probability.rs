@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use chron_db::derived::{DbPlay, WpaPlayerRow};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{AppError, AppState};
+
+// (inning, inning_side, outs, bases, run_diff) -> home win%
+// mirrors the win_expectancy matview, so the inning and run diff keys are clamped the same way
+#[derive(Clone, Default)]
+pub struct WinExpectancyTable {
+    states: HashMap<(i32, i32, i32, i32, i32), f32>,
+}
+
+impl WinExpectancyTable {
+    pub fn lookup(
+        &self,
+        inning: i32,
+        inning_side: i32,
+        outs: i32,
+        bases: i32,
+        run_diff: i32,
+    ) -> f32 {
+        let key = (
+            inning.clamp(1, 9),
+            inning_side,
+            outs,
+            bases,
+            run_diff.clamp(-10, 10),
+        );
+        match self.states.get(&key) {
+            Some(pct) => *pct,
+            // never seen this state, so just go by who's ahead
+            None => 0.5 + 0.5 * run_diff.signum() as f32,
+        }
+    }
+}
+
+pub async fn refresh_win_expectancy(ctx: AppState) -> anyhow::Result<WinExpectancyTable> {
+    info!("refreshing win expectancy");
+    let rows = ctx.db.get_win_expectancy().await?;
+    Ok(WinExpectancyTable {
+        states: rows
+            .into_iter()
+            .map(|r| {
+                (
+                    (r.inning, r.inning_side, r.outs, r.bases, r.run_diff),
+                    r.home_win_pct,
+                )
+            })
+            .collect(),
+    })
+}
+
+#[derive(Serialize, Debug)]
+pub struct WinProbabilityResponse {
+    game_id: String,
+    events: Vec<WinProbabilityEvent>,
+}
+
+// probabilities are always from the home team's point of view
+#[derive(Serialize, Debug)]
+pub struct WinProbabilityEvent {
+    index: i32,
+    inning: i32,
+    inning_side: i32,
+    outs: i32,
+    bases: i32,
+    home_score: i32,
+    away_score: i32,
+    batter_id: Option<String>,
+    pitcher_id: Option<String>,
+    outcome: Option<String>,
+    home_win_probability: f32,
+    home_wpa: f32,
+}
+
+pub async fn game_win_probability(
+    State(ctx): State<AppState>,
+    Path(game_id): Path<String>,
+) -> Result<Json<WinProbabilityResponse>, AppError> {
+    let Some(game) = ctx.db.get_game(&game_id).await? else {
+        return Err(AppError::not_found("game not found"));
+    };
+    let plays = ctx.db.get_game_plays(&game_id).await?;
+    let table = ctx.win_expectancy_cache.get(()).await?;
+
+    // only pin the last event to 0/1 once the game's actually over
+    let final_home_won = if game.state == "Complete" {
+        let score = |key: &str| {
+            game.last_update
+                .as_ref()
+                .and_then(|x| x.get(key))
+                .and_then(|x| x.as_i64())
+        };
+        score("home_score")
+            .zip(score("away_score"))
+            .map(|(home, away)| home > away)
+    } else {
+        None
+    };
+
+    Ok(Json(WinProbabilityResponse {
+        events: win_probability_events(&table, &plays, final_home_won),
+        game_id,
+    }))
+}
+
+// same logic as the base_out_states/win_probability_events matviews, but for a single
+// game so it works on live games too
+fn win_probability_events(
+    table: &WinExpectancyTable,
+    plays: &[DbPlay],
+    final_home_won: Option<bool>,
+) -> Vec<WinProbabilityEvent> {
+    let mut events = Vec::with_capacity(plays.len());
+
+    let mut wp_before = table.lookup(1, 0, 0, 0, 0);
+    let (mut home_score, mut away_score) = (0, 0);
+    let mut half = None;
+    let mut outs_before = 0;
+    for (i, play) in plays.iter().enumerate() {
+        if half != Some((play.inning, play.inning_side)) {
+            half = Some((play.inning, play.inning_side));
+            outs_before = 0;
+        }
+
+        home_score = home_score.max(play.home_score.unwrap_or(0));
+        away_score = away_score.max(play.away_score.unwrap_or(0));
+
+        // the outs counter may reset on the third out, so don't trust it blindly
+        let next_in_same_half = plays
+            .get(i + 1)
+            .map(|next| (next.inning, next.inning_side) == (play.inning, play.inning_side))
+            .unwrap_or(false);
+        let outs_after = if next_in_same_half {
+            play.outs
+                .unwrap_or(0)
+                .max(outs_before + play.outs_recorded)
+                .min(3)
+        } else {
+            3
+        };
+        let bases_after = if outs_after >= 3 { 0 } else { bases_mask(play) };
+
+        let is_last = i == plays.len() - 1;
+        let wp_after = match final_home_won {
+            Some(home_won) if is_last => {
+                if home_won {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            _ if outs_after >= 3 => {
+                let (next_inning, next_side) = if play.inning_side == 1 {
+                    (play.inning + 1, 0)
+                } else {
+                    (play.inning, 1)
+                };
+                table.lookup(next_inning, next_side, 0, 0, home_score - away_score)
+            }
+            _ => table.lookup(
+                play.inning,
+                play.inning_side,
+                outs_after,
+                bases_after,
+                home_score - away_score,
+            ),
+        };
+
+        events.push(WinProbabilityEvent {
+            index: play.index,
+            inning: play.inning,
+            inning_side: play.inning_side,
+            outs: outs_after,
+            bases: bases_after,
+            home_score,
+            away_score,
+            batter_id: play.batter_id.clone(),
+            pitcher_id: play.pitcher_id.clone(),
+            outcome: play.outcome.clone(),
+            home_win_probability: wp_after,
+            home_wpa: wp_after - wp_before,
+        });
+
+        wp_before = wp_after;
+        outs_before = outs_after;
+    }
+
+    events
+}
+
+fn bases_mask(play: &DbPlay) -> i32 {
+    [(play.on_1b, 1), (play.on_2b, 2), (play.on_3b, 4)]
+        .iter()
+        .map(|(on, bit)| if on.unwrap_or(false) { *bit } else { 0 })
+        .sum()
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WpaQuery {
+    pub season: i32,
+    #[serde(default)]
+    pub count: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WpaResponse {
+    season: i32,
+    batters: Vec<WpaPlayerRow>,
+    pitchers: Vec<WpaPlayerRow>,
+}
+
+pub async fn wpa(
+    State(ctx): State<AppState>,
+    Query(q): Query<WpaQuery>,
+) -> Result<Json<WpaResponse>, AppError> {
+    let count = q.count.unwrap_or(50);
+    let batters = ctx.db.get_wpa_leaders(q.season, false, count).await?;
+    let pitchers = ctx.db.get_wpa_leaders(q.season, true, count).await?;
+
+    Ok(Json(WpaResponse {
+        season: q.season,
+        batters,
+        pitchers,
+    }))
+}
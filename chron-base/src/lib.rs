@@ -1,4 +1,4 @@
-use std::{fmt::Display, str::FromStr};
+use std::{collections::HashMap, fmt::Display, str::FromStr};
 
 use anyhow::anyhow;
 use config::Config;
@@ -12,12 +12,49 @@ pub mod cache;
 #[derive(Deserialize)]
 pub struct ChronConfig {
     pub database_uri: String,
-    pub scylla_uri: String,
     pub maps_api_key: Option<String>,
     pub export_path: Option<String>,
+    // another chron instance to backfill versions from, eg. http://localhost:3001
+    pub mirror_url: Option<String>,
 
     #[serde(default)]
     pub jitter: bool,
+
+    #[serde(default)]
+    pub compaction: CompactionConfig,
+}
+
+// collapsing runs of identical observations, see chron-ingest's compaction worker
+#[derive(Deserialize, Debug, Clone)]
+pub struct CompactionConfig {
+    // runs the worker on a timer, the cli commands work either way
+    #[serde(default)]
+    pub enabled: bool,
+    // observations newer than this are left at full resolution, and late ones older than this
+    // get rejected once compaction has run (so keep it longer than a mirror's rescan window)
+    #[serde(default = "default_compaction_keep_days")]
+    pub keep_days: i64,
+    // per-kind keep_days overrides, by snake_case kind name (eg. `message = 1`)
+    #[serde(default)]
+    pub kinds: HashMap<String, i64>,
+    // kinds that never get compacted
+    #[serde(default)]
+    pub skip: Vec<String>,
+}
+
+fn default_compaction_keep_days() -> i64 {
+    7
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        CompactionConfig {
+            enabled: false,
+            keep_days: default_compaction_keep_days(),
+            kinds: HashMap::new(),
+            skip: Vec::new(),
+        }
+    }
 }
 
 pub fn normalize_location(s: &str) -> String {
@@ -0,0 +1,40 @@
+-- one row per game event, parsed out of the message text
+create table plays (
+    game_id text not null,
+    index int not null,
+    season smallint not null,
+    day smallint not null,
+    inning int not null,
+    inning_side int not null,
+    event text not null,
+    pitcher_id text,
+    batter_id text,
+
+    -- game state *after* the event, as reported by the event itself
+    balls int,
+    strikes int,
+    outs int,
+    on_1b boolean,
+    on_2b boolean,
+    on_3b boolean,
+    home_score int,
+    away_score int,
+
+    pitch_result text,
+    pitch_type text,
+    pitch_speed real,
+    pitch_zone int,
+    batted_ball text,
+    outcome text,
+    fielders text[] not null default '{}',
+    -- [{"runner": "name", "to": "second"}, ...]
+    advances jsonb not null default '[]',
+    outs_recorded int not null default 0,
+    runs_scored int not null default 0,
+
+    primary key (game_id, index)
+);
+
+create index plays_season_pitcher_idx on plays(season, pitcher_id);
+create index plays_season_batter_idx on plays(season, batter_id);
+create index plays_season_outcome_idx on plays(season, outcome);
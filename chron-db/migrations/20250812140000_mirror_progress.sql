@@ -0,0 +1,8 @@
+-- where `chron-ingest mirror` got to for each kind, keyed by source so switching mirrors starts over
+create table mirror_progress (
+    source text not null,
+    kind smallint not null,
+    page text not null,
+    updated_at timestamptz not null,
+    primary key (source, kind)
+);
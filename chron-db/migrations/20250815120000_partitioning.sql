@@ -0,0 +1,86 @@
+-- observations by month and game_events by season. there's deliberately no default partition,
+-- anything inserting calls these first (ChronDb caches which ones it's already made)
+create or replace function ensure_observations_partition(ts timestamptz) returns void as $$
+declare
+    month_start timestamptz := date_trunc('month', ts at time zone 'UTC') at time zone 'UTC';
+begin
+    execute format(
+        'create table if not exists %I partition of observations for values from (%L) to (%L)',
+        'observations_' || to_char(month_start at time zone 'UTC', 'YYYY_MM'),
+        month_start,
+        month_start + interval '1 month'
+    );
+exception when duplicate_table then
+    -- someone else got there first
+    null;
+end
+$$ language plpgsql;
+
+create or replace function ensure_game_events_partition(the_season smallint) returns void as $$
+begin
+    execute format(
+        'create table if not exists %I partition of game_events for values in (%s)',
+        'game_events_' || the_season,
+        the_season
+    );
+exception when duplicate_table then
+    null;
+end
+$$ language plpgsql;
+
+-- observations
+alter table observations rename to observations_unpartitioned;
+drop index observations_idx;
+
+create table observations (
+    kind smallint not null,
+    entity_id text not null,
+    timestamp timestamptz not null,
+    request_time float not null,
+    hash uuid not null
+) partition by range (timestamp);
+create index observations_idx on observations(kind, entity_id, timestamp desc);
+
+select ensure_observations_partition(month)
+    from (select distinct date_trunc('month', timestamp at time zone 'UTC') at time zone 'UTC' as month from observations_unpartitioned) months;
+select ensure_observations_partition(now() + make_interval(months => n)) from generate_series(0, 2) n;
+
+insert into observations (kind, entity_id, timestamp, request_time, hash)
+    select kind, entity_id, timestamp, request_time, hash from observations_unpartitioned;
+drop table observations_unpartitioned;
+
+-- game_events
+-- old rows from before the season column existed, the season is part of the key now so fix them up first
+update game_events ge set season = g.season, day = coalesce(g.day, ge.day)
+    from games g
+    where ge.game_id = g.game_id and ge.season = -1 and g.season is not null;
+
+alter table game_events rename to game_events_unpartitioned;
+alter table game_events_unpartitioned rename constraint game_events_pkey to game_events_unpartitioned_pkey;
+drop index game_events_observed_at_idx;
+drop index game_events_sd_idx;
+drop index game_events_season_pitcher_idx;
+drop index game_events_season_batter_idx;
+
+create table game_events (
+    game_id text not null,
+    index int not null,
+    data jsonb not null,
+    observed_at timestamptz,
+    pitcher_id text default null,
+    batter_id text default null,
+    season smallint not null default -1,
+    day smallint not null default -1,
+    -- game_id first so lookups by game don't care which partition they're in
+    primary key (game_id, index, season)
+) partition by list (season);
+create index game_events_observed_at_idx on game_events(observed_at desc);
+create index game_events_sd_idx on game_events(season, day, index);
+create index game_events_season_pitcher_idx on game_events(season, pitcher_id);
+create index game_events_season_batter_idx on game_events(season, batter_id);
+
+select ensure_game_events_partition(season) from (select distinct season from game_events_unpartitioned) seasons;
+
+insert into game_events (game_id, index, data, observed_at, pitcher_id, batter_id, season, day)
+    select game_id, index, data, observed_at, pitcher_id, batter_id, season, day from game_events_unpartitioned;
+drop table game_events_unpartitioned;
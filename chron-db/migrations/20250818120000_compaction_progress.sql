@@ -0,0 +1,6 @@
+-- how far back `chron-ingest compact` has thinned out observations for each kind. late observations
+-- before this have nothing left to fit in against, so add_version turns them away
+create table compaction_progress (
+    kind smallint primary key,
+    compacted_before timestamptz not null
+);
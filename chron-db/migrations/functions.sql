@@ -5,7 +5,26 @@ create or replace function add_version(new_kind smallint, new_entity_id text, ne
 declare
     last_observation record;
     updated_lv record;
+    current_lv record;
 begin
+    -- anything at or before the newest observation can't just be appended, hand it back (-1) for
+    -- the caller to fit in between the versions. decided while holding the latest_versions row so
+    -- a newer observation can't sneak in between the check and the insert.
+    -- a nil hash means rebuild_entity has it "locked", leave that alone like before
+    select hash from latest_versions
+        where kind = new_kind and entity_id = new_entity_id
+        for update
+        into current_lv;
+    if found and current_lv.hash != '00000000-0000-0000-0000-000000000000' then
+        select timestamp from observations
+            where kind = new_kind and entity_id = new_entity_id
+            order by timestamp desc limit 1
+            into last_observation;
+        if last_observation.timestamp is not null and new_timestamp <= last_observation.timestamp then
+            return -1;
+        end if;
+    end if;
+
     -- this should be atomic
     insert into latest_versions (kind, entity_id, seq, hash, valid_from)
         -- try to insert if this is a new object entirely
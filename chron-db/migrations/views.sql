@@ -22,7 +22,6 @@ CREATE OR REPLACE FUNCTION objectid_to_timestamp(text) RETURNS timestamptz
 drop materialized view if exists game_player_stats_exploded cascade;
 drop materialized view if exists game_player_stats_league_aggregate cascade;
 drop materialized view if exists game_player_stats_global_aggregate cascade;
-drop materialized view if exists pitches cascade;
 
 drop view if exists league_percentiles;
 drop view if exists game_player_stats_advanced;
@@ -133,4 +132,211 @@ create materialized view roster_slot_history as
     from slots_with_seq
     group by team_id, slot, seq;
 create unique index roster_slot_history_pkey_idx on roster_slot_history(team_id, slot, seq);
-create index roster_slot_history_player_idx on roster_slot_history(player_id, valid_from, valid_to);
\ No newline at end of file
+create index roster_slot_history_player_idx on roster_slot_history(player_id, valid_from, valid_to);
+
+select println('creating base/out states');
+drop materialized view if exists base_out_states cascade;
+create materialized view base_out_states as
+    with states as (
+        select
+            p.game_id,
+            p.index,
+            p.season,
+            p.day,
+            p.inning,
+            p.inning_side,
+            p.batter_id,
+            p.pitcher_id,
+            p.outcome,
+            p.runs_scored,
+            p.outcome is not null and p.outcome not in ('stolen_base', 'caught_stealing') as is_pa,
+            -- bases are a bitmask, 1 = first, 2 = second, 4 = third
+            coalesce(lag(p.outs) over w, 0) as outs_before,
+            (case when coalesce(lag(p.on_1b) over w, false) then 1 else 0 end)
+                + (case when coalesce(lag(p.on_2b) over w, false) then 2 else 0 end)
+                + (case when coalesce(lag(p.on_3b) over w, false) then 4 else 0 end) as bases_before,
+            coalesce(p.outs, 0) as outs_reported,
+            p.outs_recorded,
+            (case when coalesce(p.on_1b, false) then 1 else 0 end)
+                + (case when coalesce(p.on_2b, false) then 2 else 0 end)
+                + (case when coalesce(p.on_3b, false) then 4 else 0 end) as bases_reported,
+            (lead(p.index) over w) is null as last_in_half,
+            (lead(p.index) over g) is null as last_in_game,
+            sum(p.runs_scored) over (w rows between current row and unbounded following) as runs_rest_of_inning,
+            -- scores never go down, so this papers over events that don't report them
+            coalesce(max(p.home_score) over (g rows between unbounded preceding and 1 preceding), 0) as home_score_before,
+            coalesce(max(p.away_score) over (g rows between unbounded preceding and 1 preceding), 0) as away_score_before,
+            coalesce(max(p.home_score) over (g rows between unbounded preceding and current row), 0) as home_score_after,
+            coalesce(max(p.away_score) over (g rows between unbounded preceding and current row), 0) as away_score_after
+        from plays p
+        inner join games using (game_id)
+        where games.state = 'Complete'
+        window
+            w as (partition by p.game_id, p.inning, p.inning_side order by p.index),
+            g as (partition by p.game_id order by p.index)
+    ), with_after as (
+        select
+            *,
+            -- the outs counter may well reset on the third out, so don't trust it blindly
+            case
+                when last_in_half then 3
+                else least(3, greatest(outs_reported, outs_before + outs_recorded))
+            end as outs_after
+        from states
+    )
+    select
+        game_id, index, season, day, inning, inning_side, batter_id, pitcher_id, outcome, is_pa,
+        outs_before, bases_before,
+        outs_after, case when outs_after >= 3 then 0 else bases_reported end as bases_after,
+        runs_scored, runs_rest_of_inning,
+        home_score_before, away_score_before, home_score_after, away_score_after, last_in_game
+    from with_after;
+create unique index base_out_states_pkey_idx on base_out_states(game_id, index);
+create index base_out_states_season_batter_idx on base_out_states(season, batter_id);
+create index base_out_states_season_pitcher_idx on base_out_states(season, pitcher_id);
+
+select println('creating run expectancy matrix');
+drop materialized view if exists re24_matrix cascade;
+create materialized view re24_matrix as
+    select
+        season,
+        outs_before as outs,
+        bases_before as bases,
+        count(*)::int as occurrences,
+        avg(runs_rest_of_inning)::real as run_expectancy
+    from base_out_states
+    -- walk-offs cut the bottom of the 9th (and later) short, leave those out
+    where is_pa and outs_before < 3 and not (inning_side = 1 and inning >= 9)
+    group by season, outs_before, bases_before;
+create unique index re24_matrix_pkey_idx on re24_matrix(season, outs, bases);
+
+select println('creating win expectancy');
+drop materialized view if exists win_expectancy cascade;
+create materialized view win_expectancy as
+    with buckets as (
+        select
+            -- extra innings play like the 9th, run differential gets clamped
+            least(s.inning, 9) as inning,
+            s.inning_side,
+            s.outs_before as outs,
+            s.bases_before as bases,
+            greatest(-10, least(10, s.home_score_before - s.away_score_before)) as run_diff,
+            count(*)::int as occurrences,
+            (count(*) filter (where (g.last_update->>'home_score')::int > (g.last_update->>'away_score')::int))::int as home_wins
+        from base_out_states s
+        inner join games g using (game_id)
+        where s.inning >= 1 and s.outs_before < 3
+        group by 1, 2, 3, 4, 5
+    ), with_coarse as (
+        select
+            *,
+            sum(home_wins) over c::real / sum(occurrences) over c as coarse_pct
+        from buckets
+        window c as (partition by inning, inning_side, run_diff)
+    )
+    select
+        inning, inning_side, outs, bases, run_diff, occurrences, home_wins,
+        -- rare states get pulled towards the inning/score-only rate
+        ((home_wins + 10 * coarse_pct) / (occurrences + 10))::real as home_win_pct
+    from with_coarse;
+create unique index win_expectancy_pkey_idx on win_expectancy(inning, inning_side, outs, bases, run_diff);
+
+select println('creating win probability events');
+drop materialized view if exists win_probability_events cascade;
+create materialized view win_probability_events as
+    with after_states as (
+        select
+            s.*,
+            -- the third out moves us to the start of the next half-inning
+            case when s.outs_after >= 3 and s.inning_side = 1 then s.inning + 1 else s.inning end as next_inning,
+            case when s.outs_after >= 3 then 1 - s.inning_side else s.inning_side end as next_side,
+            case when s.outs_after >= 3 then 0 else s.outs_after end as next_outs,
+            case when s.outs_after >= 3 then 0 else s.bases_after end as next_bases,
+            (g.last_update->>'home_score')::int > (g.last_update->>'away_score')::int as home_won
+        from base_out_states s
+        inner join games g using (game_id)
+    ), with_wp as (
+        select
+            a.*,
+            (case
+                when a.last_in_game then (case when a.home_won then 1 else 0 end)
+                else coalesce(we.home_win_pct, 0.5 + 0.5 * sign(a.home_score_after - a.away_score_after))
+            end)::real as wp_after
+        from after_states a
+        left join win_expectancy we on
+            we.inning = least(a.next_inning, 9)
+            and we.inning_side = a.next_side
+            and we.outs = a.next_outs
+            and we.bases = a.next_bases
+            and we.run_diff = greatest(-10, least(10, a.home_score_after - a.away_score_after))
+    )
+    select
+        game_id, index, season, day, inning, inning_side, batter_id, pitcher_id, is_pa,
+        coalesce(
+            lag(wp_after) over w,
+            (select home_win_pct from win_expectancy where inning = 1 and inning_side = 0 and outs = 0 and bases = 0 and run_diff = 0),
+            0.5
+        )::real as wp_before,
+        wp_after
+    from with_wp
+    window w as (partition by game_id order by index);
+create unique index win_probability_events_pkey_idx on win_probability_events(game_id, index);
+create index win_probability_events_season_batter_idx on win_probability_events(season, batter_id);
+create index win_probability_events_season_pitcher_idx on win_probability_events(season, pitcher_id);
+
+select println('creating pitches');
+drop materialized view if exists pitches cascade;
+create materialized view pitches as
+    with pa_numbers as (
+        select
+            p.*,
+            p.outcome is not null and p.outcome not in ('stolen_base', 'caught_stealing') as ends_pa,
+            -- number of plate appearances that finished before this event
+            coalesce(sum(case when p.outcome is not null and p.outcome not in ('stolen_base', 'caught_stealing') then 1 else 0 end)
+                over (partition by p.game_id order by p.index rows between unbounded preceding and 1 preceding), 0) as pa_number
+        from plays p
+    ), with_before as (
+        select
+            *,
+            row_number() over w as pitch_number,
+            -- the previous pitch in the same PA can't have ended it, so its reported count is still live.
+            -- otherwise count it up ourselves
+            coalesce(lag(balls) over w, least(3, coalesce(sum(ball) over earlier, 0))) as balls_before,
+            coalesce(lag(strikes) over w, least(2, coalesce(sum(strike) over earlier, 0))) as strikes_before
+        from (
+            select
+                *,
+                case when pitch_result = 'ball' then 1 else 0 end as ball,
+                case when pitch_result in ('called_strike', 'swinging_strike', 'foul_tip', 'foul') then 1 else 0 end as strike
+            from pa_numbers
+        ) x
+        where pitch_result is not null
+        -- a caught stealing can end the inning mid-PA, the count starts over next inning
+        window
+            w as (partition by game_id, pa_number, inning, inning_side order by index),
+            earlier as (w rows between unbounded preceding and 1 preceding)
+    )
+    select
+        game_id, index, season, day, inning, inning_side, pitcher_id, batter_id,
+        pa_number::int, pitch_number::int,
+        balls_before::int, strikes_before::int,
+        -- the count is usually reset once the PA is over, so work it out ourselves
+        case
+            when ends_pa or balls is null then balls_before + (case when pitch_result = 'ball' then 1 else 0 end)
+            else balls
+        end::int as balls_after,
+        case
+            when ends_pa or strikes is null then strikes_before + (case
+                when pitch_result in ('called_strike', 'swinging_strike', 'foul_tip') then 1
+                when pitch_result = 'foul' and strikes_before < 2 then 1
+                else 0
+            end)
+            else strikes
+        end::int as strikes_after,
+        pitch_result, pitch_type, pitch_speed, pitch_zone, batted_ball,
+        case when ends_pa then outcome end as outcome,
+        ends_pa
+    from with_before;
+create unique index pitches_pkey_idx on pitches(game_id, index);
+create index pitches_season_pitcher_idx on pitches(season, pitcher_id);
+create index pitches_season_batter_idx on pitches(season, batter_id);
@@ -3,7 +3,7 @@ use chron_base::{StatKey, objectid_to_timestamp};
 use compact_str::CompactString;
 use futures::{Stream, TryStreamExt};
 use sea_query::{
-    Asterisk, Cond, Expr, ExprTrait, Func, InsertStatement, IntoIden, OnConflict,
+    Alias, Asterisk, Cond, Expr, ExprTrait, Func, InsertStatement, IntoIden, OnConflict,
     PostgresQueryBuilder, Query, SelectStatement, SimpleExpr,
 };
 use sea_query_binder::SqlxBinder;
@@ -102,6 +102,37 @@ pub struct StatFilter {
     gte: Option<u32>,
 }
 
+impl StatFilter {
+    fn exprs(&self, value: SimpleExpr) -> Vec<SimpleExpr> {
+        let mut exprs = Vec::new();
+        if let Some(v) = self.eq {
+            exprs.push(value.clone().eq(v));
+        }
+        if let Some(v) = self.lt {
+            exprs.push(value.clone().lt(v));
+        }
+        if let Some(v) = self.gt {
+            exprs.push(value.clone().gt(v));
+        }
+        if let Some(v) = self.lte {
+            exprs.push(value.clone().lte(v));
+        }
+        if let Some(v) = self.gte {
+            exprs.push(value.clone().gte(v));
+        }
+        exprs
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsAccumulate {
+    // running season-to-date total, starts over every season
+    Cumulative,
+    // total over the last n days of the season (or the last n games, when grouping by game),
+    // so the first days of a season only cover what's been played so far
+    Rolling(u32),
+}
+
 #[derive(Clone)]
 pub struct StatsQueryNew {
     pub start: Option<(i32, i32)>,
@@ -127,6 +158,10 @@ pub struct StatsQueryNew {
 
     pub fields: Vec<StatKey>,
     pub filters: Vec<(StatKey, StatFilter)>,
+
+    // emit running totals per group over (season, day) instead of plain sums
+    // implies grouping by day
+    pub accumulate: Option<StatsAccumulate>,
 }
 
 #[derive(sqlx::Type, Debug, Clone, Copy, Serialize, Deserialize, IntoStaticStr, PartialEq, Eq)]
@@ -174,14 +209,20 @@ pub struct StatsRow {
     pub values: [u32; StatKey::COUNT],
 }
 
+// stat columns that weren't selected just come out as 0
+fn stat_values(row: &PgRow) -> [u32; StatKey::COUNT] {
+    let mut values = [0u32; StatKey::COUNT];
+    for (i, sk) in StatKey::VARIANTS.iter().enumerate() {
+        let name: &'static str = sk.into();
+        // dbg!(i, name, row.try_get::<i32, _>(name));
+        values[i] = row.try_get::<i32, _>(name).unwrap_or(0) as u32;
+    }
+    values
+}
+
 impl FromRow<'_, PgRow> for StatsRow {
     fn from_row(row: &PgRow) -> sqlx::Result<Self> {
-        let mut values = [0u32; StatKey::COUNT];
-        for (i, sk) in StatKey::VARIANTS.iter().enumerate() {
-            let name: &'static str = sk.into();
-            // dbg!(i, name, row.try_get::<i32, _>(name));
-            values[i] = row.try_get::<i32, _>(name).unwrap_or(0) as u32;
-        }
+        let values = stat_values(row);
 
         let team_location: Option<CompactString> = row.try_get("team_location").ok();
         let team_name: Option<CompactString> = row.try_get("team_name").ok();
@@ -215,25 +256,456 @@ impl FromRow<'_, PgRow> for StatsRow {
     }
 }
 
+pub struct GetGameLogQuery {
+    pub player: String,
+    pub start: Option<(i32, i32)>,
+    pub end: Option<(i32, i32)>,
+    pub fields: Vec<StatKey>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GameLogRow {
+    pub game_id: String,
+    pub season: i16,
+    pub day: i16,
+    pub day_special: Option<String>,
+    pub state: Option<String>,
+    pub team_id: String,
+    pub player_name: Option<String>,
+    pub opponent_id: Option<String>,
+    pub opponent_name: Option<String>,
+    pub home: bool,
+    pub home_score: Option<i32>,
+    pub away_score: Option<i32>,
+    pub values: [u32; StatKey::COUNT],
+}
+
+impl GameLogRow {
+    pub fn team_score(&self) -> Option<i32> {
+        if self.home {
+            self.home_score
+        } else {
+            self.away_score
+        }
+    }
+
+    pub fn opponent_score(&self) -> Option<i32> {
+        if self.home {
+            self.away_score
+        } else {
+            self.home_score
+        }
+    }
+
+    // W/L (or T, just in case), only once the game is actually over
+    pub fn result(&self) -> Option<&'static str> {
+        if self.state.as_deref() != Some("Complete") {
+            return None;
+        }
+
+        let (ours, theirs) = (self.team_score()?, self.opponent_score()?);
+        Some(match ours.cmp(&theirs) {
+            std::cmp::Ordering::Greater => "W",
+            std::cmp::Ordering::Less => "L",
+            std::cmp::Ordering::Equal => "T",
+        })
+    }
+}
+
+impl FromRow<'_, PgRow> for GameLogRow {
+    fn from_row(row: &PgRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            game_id: row.try_get("game_id")?,
+            season: row.try_get("season")?,
+            day: row.try_get("day")?,
+            day_special: row.try_get("day_special")?,
+            state: row.try_get("state")?,
+            team_id: row.try_get("team_id")?,
+            player_name: row.try_get("player_name")?,
+            opponent_id: row.try_get("opponent_id")?,
+            opponent_name: row.try_get("opponent_name")?,
+            home: row.try_get("home")?,
+            home_score: row.try_get("home_score")?,
+            away_score: row.try_get("away_score")?,
+            values: stat_values(row),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, FromRow)]
+pub struct DbGameEvent {
+    pub game_id: String,
+    pub index: i32,
+    pub season: i16,
+    pub day: i16,
+    pub data: serde_json::Value,
+    pub pitcher_id: Option<String>,
+    pub batter_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct DbPlay {
+    pub game_id: String,
+    pub index: i32,
+    pub season: i16,
+    pub day: i16,
+    pub inning: i32,
+    pub inning_side: i32,
+    pub event: String,
+    pub pitcher_id: Option<String>,
+    pub batter_id: Option<String>,
+
+    pub balls: Option<i32>,
+    pub strikes: Option<i32>,
+    pub outs: Option<i32>,
+    pub on_1b: Option<bool>,
+    pub on_2b: Option<bool>,
+    pub on_3b: Option<bool>,
+    pub home_score: Option<i32>,
+    pub away_score: Option<i32>,
+
+    pub pitch_result: Option<String>,
+    pub pitch_type: Option<String>,
+    pub pitch_speed: Option<f32>,
+    pub pitch_zone: Option<i32>,
+    pub batted_ball: Option<String>,
+    pub outcome: Option<String>,
+    pub fielders: Vec<String>,
+    pub advances: serde_json::Value,
+    pub outs_recorded: i32,
+    pub runs_scored: i32,
+}
+
+#[derive(Default)]
+pub struct GetGameResultsQuery {
+    pub season: Option<i32>,
+    pub through_day: Option<i32>,
+    pub team: Option<String>,
+    pub opponent: Option<String>,
+    // leave out postseason/special day games
+    pub regular_season_only: bool,
+}
+
+#[derive(FromRow, Debug, Clone)]
+pub struct DbGameResult {
+    pub game_id: String,
+    pub season: i32,
+    pub day: i32,
+    pub day_special: Option<String>,
+    pub home_team_id: String,
+    pub away_team_id: String,
+    pub home_score: i32,
+    pub away_score: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct GamePlayerLine {
+    pub team_id: String,
+    pub player_id: String,
+    pub player_name: Option<String>,
+    pub values: [u32; StatKey::COUNT],
+}
+
+impl GamePlayerLine {
+    pub fn get(&self, key: StatKey) -> u32 {
+        self.values[key as usize]
+    }
+}
+
+impl FromRow<'_, PgRow> for GamePlayerLine {
+    fn from_row(row: &PgRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            team_id: row.try_get("team_id")?,
+            player_id: row.try_get("player_id")?,
+            player_name: row.try_get("player_name")?,
+            values: stat_values(row),
+        })
+    }
+}
+
+// highest score seen by the end of each half-inning
+#[derive(FromRow, Debug, Clone)]
+pub struct DbHalfInningScore {
+    pub inning: i32,
+    pub inning_side: i32,
+    pub away_score: Option<i32>,
+    pub home_score: Option<i32>,
+}
+
+pub struct GetMatchupsQuery {
+    pub batter: Option<String>,
+    pub pitcher: Option<String>,
+    pub start: Option<(i32, i32)>,
+    pub end: Option<(i32, i32)>,
+    // lump every opponent into one row instead of one row per opponent
+    pub combined: bool,
+}
+
+#[derive(FromRow, Serialize, Debug, Clone)]
+pub struct MatchupRow {
+    pub batter_id: Option<String>,
+    pub batter_name: Option<String>,
+    pub pitcher_id: Option<String>,
+    pub pitcher_name: Option<String>,
+    pub pa: i32,
+    pub ab: i32,
+    pub h: i32,
+    pub doubles: i32,
+    pub triples: i32,
+    pub hr: i32,
+    pub bb: i32,
+    pub hbp: i32,
+    pub k: i32,
+    pub sf: i32,
+}
+
+#[derive(FromRow, Serialize, Debug, Clone)]
+pub struct Re24MatrixRow {
+    pub season: i16,
+    pub outs: i32,
+    // bitmask, 1 = first, 2 = second, 4 = third
+    pub bases: i32,
+    pub occurrences: i32,
+    pub run_expectancy: f32,
+}
+
+#[derive(FromRow, Serialize, Debug, Clone)]
+pub struct Re24PlayerRow {
+    pub player_id: String,
+    pub player_name: Option<String>,
+    pub pa: i32,
+    pub re24: f32,
+}
+
+#[derive(FromRow, Serialize, Debug, Clone)]
+pub struct WinExpectancyRow {
+    pub inning: i32,
+    pub inning_side: i32,
+    pub outs: i32,
+    pub bases: i32,
+    pub run_diff: i32,
+    pub occurrences: i32,
+    pub home_win_pct: f32,
+}
+
+#[derive(FromRow, Serialize, Debug, Clone)]
+pub struct WpaPlayerRow {
+    pub player_id: String,
+    pub player_name: Option<String>,
+    pub pa: i32,
+    pub wpa: f32,
+}
+
+#[derive(FromRow, Serialize, Debug, Clone)]
+pub struct DbPitch {
+    pub game_id: String,
+    pub index: i32,
+    pub season: i16,
+    pub day: i16,
+    pub inning: i32,
+    pub inning_side: i32,
+    pub pitcher_id: Option<String>,
+    pub batter_id: Option<String>,
+    // both count from 0 within the game
+    pub pa_number: i32,
+    pub pitch_number: i32,
+    pub balls_before: i32,
+    pub strikes_before: i32,
+    pub balls_after: i32,
+    pub strikes_after: i32,
+    pub pitch_result: String,
+    pub pitch_type: Option<String>,
+    pub pitch_speed: Option<f32>,
+    pub pitch_zone: Option<i32>,
+    pub batted_ball: Option<String>,
+    // only set on the pitch that ends the PA
+    pub outcome: Option<String>,
+}
+
+pub struct GetPitchStatsQuery {
+    pub season: i32,
+    pub pitcher: Option<String>,
+    // one row per pitcher and pitch type instead of one per pitcher
+    pub by_type: bool,
+    pub min_pitches: i64,
+    pub count: i64,
+}
+
+// rates are out of all pitches unless noted otherwise
+#[derive(FromRow, Serialize, Debug, Clone)]
+pub struct PitchStatsRow {
+    pub pitcher_id: String,
+    pub pitcher_name: Option<String>,
+    // only filled in with by_type
+    pub pitch_type: Option<String>,
+    pub pitches: i32,
+    pub pa: i32,
+    pub pitches_per_pa: Option<f32>,
+    pub strike_rate: f32,
+    pub ball_rate: f32,
+    pub called_strike_rate: f32,
+    pub swinging_strike_rate: f32,
+    pub foul_rate: f32,
+    pub in_play_rate: f32,
+    // swinging strikes out of swings
+    pub whiff_rate: Option<f32>,
+    // out of PAs
+    pub first_pitch_strike_rate: Option<f32>,
+    pub avg_speed: Option<f32>,
+    pub max_speed: Option<f32>,
+}
+
+// per-player season totals (one row per league they played in) and rate stats, shared by the
+// percentile queries. the rates are computed from a `rate_input` cte that goes in between, with
+// the same columns as totals.
+// $1 season, $2 league, $3/$4 day range, $5 min pa, $6 min outs.
+// rates are null for players under the thresholds so they drop out of the distributions
+const PLAYER_TOTALS_CTE: &str = r"with totals as (
+    select
+        s.player_id,
+        t.league_id,
+        coalesce(sum(s.plate_appearances), 0)::float8 as pa,
+        coalesce(sum(s.at_bats), 0)::float8 as ab,
+        coalesce(sum(s.singles), 0)::float8 as singles,
+        coalesce(sum(s.doubles), 0)::float8 as doubles,
+        coalesce(sum(s.triples), 0)::float8 as triples,
+        coalesce(sum(s.home_runs), 0)::float8 as home_runs,
+        coalesce(sum(s.walked), 0)::float8 as walked,
+        coalesce(sum(s.hit_by_pitch), 0)::float8 as hbp,
+        coalesce(sum(s.sac_flies), 0)::float8 as sf,
+        coalesce(sum(s.stolen_bases), 0)::float8 as sb,
+        coalesce(sum(s.caught_stealing), 0)::float8 as cs,
+        coalesce(sum(s.outs), 0)::float8 as outs,
+        coalesce(sum(s.earned_runs), 0)::float8 as er,
+        coalesce(sum(s.hits_allowed), 0)::float8 as h_allowed,
+        coalesce(sum(s.walks), 0)::float8 as bb_allowed,
+        coalesce(sum(s.hit_batters), 0)::float8 as hbp_allowed,
+        coalesce(sum(s.strikeouts), 0)::float8 as k,
+        coalesce(sum(s.home_runs_allowed), 0)::float8 as hr_allowed
+    from game_player_stats_exploded s
+    left join teams t on t.team_id = s.team_id
+    where s.season = $1
+        and ($2::text is null or t.league_id = $2)
+        and ($3::smallint is null or s.day >= $3)
+        and ($4::smallint is null or s.day <= $4)
+    group by s.player_id, t.league_id
+)";
+
+const PLAYER_TOTALS_COLUMNS: &str = "pa ab singles doubles triples home_runs walked hbp sf sb cs outs er h_allowed bb_allowed hbp_allowed k hr_allowed";
+
+const PLAYER_RATES_CTE: &str = r", rates as (
+    select
+        *,
+        case when pa >= $5 and ab > 0 then (singles + doubles + triples + home_runs) / ab end as ba,
+        case when pa >= $5 and ab + walked + hbp + sf > 0 then (singles + doubles + triples + home_runs + walked + hbp) / (ab + walked + hbp + sf) end as obp,
+        case when pa >= $5 and ab > 0 then (singles + 2 * doubles + 3 * triples + 4 * home_runs) / ab end as slg,
+        case when pa >= $5 and ab > 0 then
+            (singles + doubles + triples + home_runs + walked + hbp) / nullif(ab + walked + hbp + sf, 0)
+            + (singles + 2 * doubles + 3 * triples + 4 * home_runs) / ab
+        end as ops,
+        case when pa >= $5 and sb + cs > 0 then sb / (sb + cs) end as sb_success,
+        case when outs >= $6 and outs > 0 then 27 * er / outs end as era,
+        case when outs >= $6 and outs > 0 then 3 * (bb_allowed + h_allowed) / outs end as whip,
+        case when outs >= $6 and outs > 0 then 3 * (13 * hr_allowed + 3 * (bb_allowed + hbp_allowed) - 2 * k) / outs end as fip_base,
+        case when outs >= $6 and outs > 0 then 27 * h_allowed / outs end as h9,
+        case when outs >= $6 and outs > 0 then 27 * k / outs end as k9,
+        case when outs >= $6 and outs > 0 then 27 * bb_allowed / outs end as bb9,
+        case when outs >= $6 and outs > 0 then 27 * hr_allowed / outs end as hr9
+    from rate_input
+)
+";
+
+pub struct GetPercentilesQuery {
+    pub season: i32,
+    pub league: Option<String>,
+    pub start_day: Option<i32>,
+    pub end_day: Option<i32>,
+    // players below these don't count towards the batting/pitching distributions
+    pub min_pa: i32,
+    pub min_outs: i32,
+}
+
 #[derive(FromRow, Debug, Clone, Serialize)]
 pub struct PercentileStats {
     pub season: i32,
     pub league_id: Option<String>,
+    // the row covers every league in the query, league_id is meaningless
+    pub all_leagues: bool,
     pub percentile: f32,
 
-    pub ba: f32,
-    pub obp: f32,
-    pub slg: f32,
-    pub ops: f32,
-    pub sb_success: f32,
-    pub era: f32,
-    pub whip: f32,
-    pub fip_base: f32,
-    pub fip_const: f32,
-    pub h9: f32,
-    pub k9: f32,
-    pub bb9: f32,
-    pub hr9: f32,
+    // None if nobody qualified
+    pub ba: Option<f32>,
+    pub obp: Option<f32>,
+    pub slg: Option<f32>,
+    pub ops: Option<f32>,
+    pub sb_success: Option<f32>,
+    pub era: Option<f32>,
+    pub whip: Option<f32>,
+    pub fip_base: Option<f32>,
+    // league-wide constant, the same for every percentile
+    pub fip_const: Option<f32>,
+    pub h9: Option<f32>,
+    pub k9: Option<f32>,
+    pub bb9: Option<f32>,
+    pub hr9: Option<f32>,
+}
+
+#[derive(FromRow, Debug, Clone, Serialize)]
+pub struct PlayerRates {
+    pub league_id: Option<String>,
+    pub pa: i32,
+    pub outs: i32,
+
+    // same as PercentileStats, None if under the thresholds
+    pub ba: Option<f32>,
+    pub obp: Option<f32>,
+    pub slg: Option<f32>,
+    pub ops: Option<f32>,
+    pub sb_success: Option<f32>,
+    pub era: Option<f32>,
+    pub whip: Option<f32>,
+    pub fip_base: Option<f32>,
+    pub h9: Option<f32>,
+    pub k9: Option<f32>,
+    pub bb9: Option<f32>,
+    pub hr9: Option<f32>,
+}
+
+// season totals for the leaderboards, one row per player
+#[derive(FromRow, Debug, Clone)]
+pub struct LeaderTotals {
+    pub player_id: String,
+    pub player_name: Option<String>,
+    // whoever they played for most recently
+    pub team_id: Option<String>,
+    // completed games that team has played this season
+    pub team_games: i32,
+
+    pub plate_appearances: i32,
+    pub at_bats: i32,
+    pub singles: i32,
+    pub doubles: i32,
+    pub triples: i32,
+    pub home_runs: i32,
+    pub walked: i32,
+    pub hit_by_pitch: i32,
+    pub sac_flies: i32,
+    pub struck_out: i32,
+    pub stolen_bases: i32,
+    pub caught_stealing: i32,
+    pub runs: i32,
+    pub runs_batted_in: i32,
+
+    pub outs: i32,
+    pub earned_runs: i32,
+    pub hits_allowed: i32,
+    pub walks: i32,
+    pub hit_batters: i32,
+    pub strikeouts: i32,
+    pub home_runs_allowed: i32,
+    pub wins: i32,
+    pub losses: i32,
+    pub saves: i32,
 }
 
 #[derive(Serialize, Deserialize, Debug, FromRow)]
@@ -317,33 +789,132 @@ impl ChronDb {
         Ok(with_page_token(res))
     }
 
+    pub async fn get_percentile_seasons(&self) -> anyhow::Result<Vec<i32>> {
+        let res = sqlx::query_scalar("select distinct season::int from games order by 1")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(res)
+    }
+
     pub async fn get_league_percentiles(
         &self,
         percentiles: &[f32],
-        season: i32,
+        q: &GetPercentilesQuery,
     ) -> anyhow::Result<Vec<PercentileStats>> {
-        let mut q = String::new();
-        q.push_str("select season, league_id,");
-
-        let cols = "ba obp slg ops sb_success era whip fip_base fip_const h9 k9 bb9 hr9";
-        for col in cols.split_ascii_whitespace() {
-            q.push_str(&format!("unnest({}) as {}, ", col, col));
-        }
-        q.push_str(" unnest($1) as percentile from league_percentiles($1::real[]) where ");
+        let mut sql =
+            format!("{PLAYER_TOTALS_CTE}, rate_input as (select * from totals){PLAYER_RATES_CTE}");
+        sql.push_str(
+            "select $1::int as season, league_id, grouping(league_id) = 1 as all_leagues, ",
+        );
 
+        let cols = "ba obp slg ops sb_success era whip fip_base h9 k9 bb9 hr9";
         for col in cols.split_ascii_whitespace() {
-            q.push_str(&format!("{} is distinct from null and ", col));
+            sql.push_str(&format!(
+                "unnest(percentile_cont($7::float8[]) within group (order by {col}))::real as {col}, "
+            ));
         }
-        q.push_str(" season = $2");
-
-        let res = sqlx::query_as(&q)
-            .bind(&percentiles)
-            .bind(season)
+        sql.push_str(
+            r"(27.0 * sum(er) / nullif(sum(outs), 0)
+                - 3.0 * (13 * sum(hr_allowed) + 3 * (sum(bb_allowed) + sum(hbp_allowed)) - 2 * sum(k)) / nullif(sum(outs), 0))::real as fip_const,
+            unnest($7::real[]) as percentile
+            from rates
+            group by grouping sets ((league_id), ())",
+        );
+
+        let res = sqlx::query_as(&sql)
+            .bind(q.season as i16)
+            .bind(&q.league)
+            .bind(q.start_day.map(|x| x as i16))
+            .bind(q.end_day.map(|x| x as i16))
+            .bind(q.min_pa)
+            .bind(q.min_outs)
+            .bind(percentiles)
             .fetch_all(&self.pool)
             .await?;
         Ok(res)
     }
 
+    // one row per league the player appeared in, most playing time first
+    pub async fn get_player_rates(
+        &self,
+        player_id: &str,
+        q: &GetPercentilesQuery,
+    ) -> anyhow::Result<Option<PlayerRates>> {
+        let cols: Vec<String> = "ba obp slg ops sb_success era whip fip_base h9 k9 bb9 hr9"
+            .split_ascii_whitespace()
+            .map(|col| format!("{col}::real"))
+            .collect();
+        // a player who moved leagues mid-season gets one line for the whole season, labelled with
+        // the league they played the most in
+        let sums: Vec<String> = PLAYER_TOTALS_COLUMNS
+            .split_ascii_whitespace()
+            .map(|col| format!("sum({col}) as {col}"))
+            .collect();
+        let sql = format!(
+            "{PLAYER_TOTALS_CTE}, rate_input as (
+                select player_id, (array_agg(league_id order by pa + outs desc))[1] as league_id, {}
+                from totals where player_id = $7 group by player_id
+            ){PLAYER_RATES_CTE} select league_id, pa::int, outs::int, {} from rates",
+            sums.join(", "),
+            cols.join(", ")
+        );
+
+        let res = sqlx::query_as(&sql)
+            .bind(q.season as i16)
+            .bind(&q.league)
+            .bind(q.start_day.map(|x| x as i16))
+            .bind(q.end_day.map(|x| x as i16))
+            .bind(q.min_pa)
+            .bind(q.min_outs)
+            .bind(player_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(res)
+    }
+
+    pub async fn get_leader_totals(
+        &self,
+        season: i32,
+        league: Option<&str>,
+    ) -> anyhow::Result<Vec<LeaderTotals>> {
+        let cols = "plate_appearances at_bats singles doubles triples home_runs walked hit_by_pitch sac_flies struck_out stolen_bases caught_stealing runs runs_batted_in outs earned_runs hits_allowed walks hit_batters strikeouts home_runs_allowed wins losses saves";
+        let sums: Vec<String> = cols
+            .split_ascii_whitespace()
+            .map(|col| format!("coalesce(sum(s.{col}), 0)::int as {col}"))
+            .collect();
+
+        let res = sqlx::query_as(&format!(
+            r"with team_games as (
+                -- qualification is per scheduled (regular season) game, postseason/special days don't raise the bar
+                select team_id, count(*)::int as team_games from (
+                    select home_team_id as team_id from games where season = $1 and state = 'Complete' and day_special is null
+                    union all
+                    select away_team_id as team_id from games where season = $1 and state = 'Complete' and day_special is null
+                ) x
+                group by team_id
+            ), totals as (
+                select
+                    s.player_id,
+                    (array_agg(s.player_name order by s.day desc))[1] as player_name,
+                    (array_agg(s.team_id order by s.day desc))[1] as team_id,
+                    {}
+                from game_player_stats_exploded s
+                left join teams t on t.team_id = s.team_id
+                where s.season = $1 and ($2::text is null or t.league_id = $2)
+                group by s.player_id
+            )
+            select totals.*, coalesce(team_games.team_games, 0) as team_games
+            from totals
+            left join team_games using (team_id)",
+            sums.join(", ")
+        ))
+        .bind(season as i16)
+        .bind(league)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(res)
+    }
+
     pub async fn get_league_averages(&self, season: i16) -> anyhow::Result<Vec<AverageStats>> {
         let res =
             sqlx::query_as("select * from game_player_stats_league_aggregate where season = $1")
@@ -402,6 +973,133 @@ impl ChronDb {
         Ok(res)
     }
 
+    pub async fn get_game_log(&self, q: GetGameLogQuery) -> anyhow::Result<Vec<GameLogRow>> {
+        // the opponent is whichever side of the game this player's team wasn't on
+        let opponent_id = "case when games.home_team_id = game_player_stats_exploded.team_id then games.away_team_id else games.home_team_id end";
+
+        let mut qq = Query::select()
+            .columns([
+                (Idens::GamePlayerStatsExploded, Idens::GameId),
+                (Idens::GamePlayerStatsExploded, Idens::Season),
+                (Idens::GamePlayerStatsExploded, Idens::Day),
+                (Idens::GamePlayerStatsExploded, Idens::TeamId),
+                (Idens::GamePlayerStatsExploded, Idens::PlayerName),
+            ])
+            .columns([
+                (Idens::Games, Idens::DaySpecial),
+                (Idens::Games, Idens::State),
+            ])
+            .expr_as(
+                Expr::cust("games.home_team_id = game_player_stats_exploded.team_id"),
+                "home",
+            )
+            .expr_as(Expr::cust(opponent_id), "opponent_id")
+            .expr_as(
+                Expr::cust("teams.location || ' ' || teams.name"),
+                "opponent_name",
+            )
+            .expr_as(
+                Expr::cust("(games.last_update->>'home_score')::int"),
+                "home_score",
+            )
+            .expr_as(
+                Expr::cust("(games.last_update->>'away_score')::int"),
+                "away_score",
+            )
+            .from(Idens::GamePlayerStatsExploded)
+            .inner_join(
+                Idens::Games,
+                Expr::col((Idens::GamePlayerStatsExploded, Idens::GameId))
+                    .equals((Idens::Games, Idens::GameId)),
+            )
+            .left_join(
+                Idens::Teams,
+                Expr::cust(format!("teams.team_id = ({})", opponent_id)),
+            )
+            .and_where(Expr::col((Idens::GamePlayerStatsExploded, Idens::PlayerId)).eq(&q.player))
+            .order_by_columns([
+                (
+                    (Idens::GamePlayerStatsExploded, Idens::Season),
+                    sea_query::Order::Asc,
+                ),
+                (
+                    (Idens::GamePlayerStatsExploded, Idens::Day),
+                    sea_query::Order::Asc,
+                ),
+                (
+                    (Idens::GamePlayerStatsExploded, Idens::GameId),
+                    sea_query::Order::Asc,
+                ),
+            ])
+            .to_owned();
+
+        for x in &q.fields {
+            let name: &'static str = x.into();
+            qq = qq
+                .expr_as(
+                    Expr::cust(format!("coalesce(game_player_stats_exploded.{}, 0)", name))
+                        .cast_as("int"),
+                    name,
+                )
+                .to_owned();
+        }
+
+        if let Some((s, d)) = q.start {
+            qq = qq
+                .and_where(
+                    Expr::tuple([
+                        Expr::col((Idens::GamePlayerStatsExploded, Idens::Season)).into(),
+                        Expr::col((Idens::GamePlayerStatsExploded, Idens::Day)).into(),
+                    ])
+                    .gte(Expr::tuple([Expr::value(s as i16), Expr::value(d as i16)])),
+                )
+                .to_owned();
+        }
+
+        if let Some((s, d)) = q.end {
+            qq = qq
+                .and_where(
+                    Expr::tuple([
+                        Expr::col((Idens::GamePlayerStatsExploded, Idens::Season)).into(),
+                        Expr::col((Idens::GamePlayerStatsExploded, Idens::Day)).into(),
+                    ])
+                    .lte(Expr::tuple([Expr::value(s as i16), Expr::value(d as i16)])),
+                )
+                .to_owned();
+        }
+
+        let (q, vals) = qq.build_sqlx(PostgresQueryBuilder);
+        let res = sqlx::query_as_with(&q, vals).fetch_all(&self.pool).await?;
+        Ok(res)
+    }
+
+    // final scores of every finished game, in the order they were played
+    pub async fn get_game_results(
+        &self,
+        q: GetGameResultsQuery,
+    ) -> anyhow::Result<Vec<DbGameResult>> {
+        let res = sqlx::query_as(
+            r"select game_id, season, day, day_special, home_team_id, away_team_id, (last_update->>'home_score')::int as home_score, (last_update->>'away_score')::int as away_score
+            from games
+            where state = 'Complete'
+                and (not $5 or day_special is null)
+                and ($1::int is null or season = $1)
+                and ($2::int is null or day <= $2)
+                and ($3::text is null or $3 in (home_team_id, away_team_id))
+                and ($4::text is null or $4 in (home_team_id, away_team_id))
+                and last_update->>'home_score' is not null and last_update->>'away_score' is not null
+            order by season, day, game_id",
+        )
+        .bind(q.season)
+        .bind(q.through_day)
+        .bind(q.team)
+        .bind(q.opponent)
+        .bind(q.regular_season_only)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(res)
+    }
+
     pub fn get_stats(
         &self,
         q: StatsQueryNew,
@@ -421,29 +1119,36 @@ impl ChronDb {
             qq = qq.expr_as(col_sum(name).cast_as("int"), name);
         }
 
-        if let Some(count) = q.count {
+        // when accumulating, the per-day sums get wrapped in a window query below
+        // so sorting/limiting/filtering has to happen on the outside instead
+        let accumulating = q.accumulate.is_some();
+
+        // non-stat columns in the output, and which of those identify a "series" to accumulate over
+        let mut key_columns: Vec<&'static str> = Vec::new();
+        let mut partition_columns: Vec<&'static str> = Vec::new();
+
+        if let Some(count) = q.count.filter(|_| !accumulating) {
             qq = qq.limit(count);
         }
 
         let mut needs_teams_table_join = false;
 
-        if let Some(player) = q.player {
-            qq = qq.and_where(
-                Expr::col((Idens::GamePlayerStatsExploded, Idens::PlayerId)).eq(&player),
-            );
+        if let Some(player) = &q.player {
+            qq = qq
+                .and_where(Expr::col((Idens::GamePlayerStatsExploded, Idens::PlayerId)).eq(player));
         }
 
-        if let Some(team) = q.team {
-            qq = qq.and_where(Expr::col((Idens::GamePlayerStatsExploded, Idens::TeamId)).eq(&team));
+        if let Some(team) = &q.team {
+            qq = qq.and_where(Expr::col((Idens::GamePlayerStatsExploded, Idens::TeamId)).eq(team));
         }
 
-        if let Some(league) = q.league {
+        if let Some(league) = &q.league {
             needs_teams_table_join = true;
-            qq = qq.and_where(Expr::col((Idens::Teams, Idens::LeagueId)).eq(&league));
+            qq = qq.and_where(Expr::col((Idens::Teams, Idens::LeagueId)).eq(league));
         }
 
-        if let Some(game) = q.game {
-            qq = qq.and_where(Expr::col(Idens::GameId).eq(&game));
+        if let Some(game) = &q.game {
+            qq = qq.and_where(Expr::col(Idens::GameId).eq(game));
         }
 
         if let Some(slot) = q.slot {
@@ -451,11 +1156,12 @@ impl ChronDb {
             qq = qq.and_where(Expr::col(Idens::Slot).eq(name));
         }
 
-        if q.group_day {
+        if q.group_day || accumulating {
             qq = qq
                 .group_by_columns([Idens::Season, Idens::Day])
                 .column(Idens::Season)
                 .column(Idens::Day);
+            key_columns.extend(["season", "day"]);
         } else if q.group_season {
             qq = qq.group_by_col(Idens::Season).column(Idens::Season);
         }
@@ -464,6 +1170,8 @@ impl ChronDb {
             qq = qq
                 .group_by_col((Idens::GamePlayerStatsExploded, Idens::PlayerId))
                 .column((Idens::GamePlayerStatsExploded, Idens::PlayerId));
+            key_columns.push("player_id");
+            partition_columns.push("player_id");
 
             if q.include_names && !q.group_player_name {
                 let full_name = Func::cust(Idens::AnyValue).arg(Expr::col((
@@ -471,6 +1179,7 @@ impl ChronDb {
                     Idens::PlayerName,
                 )));
                 qq = qq.expr_as(full_name, "player_name");
+                key_columns.push("player_name");
             }
         }
 
@@ -478,6 +1187,8 @@ impl ChronDb {
             qq = qq
                 .group_by_col((Idens::GamePlayerStatsExploded, Idens::TeamId))
                 .column((Idens::GamePlayerStatsExploded, Idens::TeamId));
+            key_columns.push("team_id");
+            partition_columns.push("team_id");
 
             if q.include_names {
                 needs_teams_table_join = true;
@@ -489,24 +1200,33 @@ impl ChronDb {
                 // but really we should just alter that table into having a `full_name` column...
                 qq = qq.expr_as(location, "team_location");
                 qq = qq.expr_as(name, "team_name");
+                key_columns.extend(["team_location", "team_name"]);
             }
         }
 
         if q.group_league {
             needs_teams_table_join = true;
             qq = qq.group_by_col(Idens::LeagueId).column(Idens::LeagueId);
+            key_columns.push("league_id");
+            partition_columns.push("league_id");
         }
 
         if q.group_game {
+            // not a partition column, each game is one step along the series
             qq = qq.group_by_col(Idens::GameId).column(Idens::GameId);
+            key_columns.push("game_id");
         }
 
         if q.group_slot {
             qq = qq.group_by_col(Idens::Slot).column(Idens::Slot);
+            key_columns.push("slot");
+            partition_columns.push("slot");
         }
 
         if q.group_player_name {
             qq = qq.group_by_col(Idens::PlayerName).column(Idens::PlayerName);
+            key_columns.push("player_name");
+            partition_columns.push("player_name");
         }
 
         if let Some((s, d)) = q.start {
@@ -544,31 +1264,27 @@ impl ChronDb {
         }
         qq = qq.cond_having(nonzero_cond);
 
-        if let Some(sort) = q.sort {
-            let name: &'static str = sort.into();
-            qq = qq.order_by_expr(col_sum(name), sea_query::Order::Desc);
-        }
-
-        for (filter_key, filter) in q.filters {
-            let name: &'static str = filter_key.into();
-            if let Some(v) = filter.eq {
-                qq = qq.and_having(col_sum(name).eq(v));
+        if !accumulating {
+            if let Some(sort) = q.sort {
+                let name: &'static str = sort.into();
+                qq = qq.order_by_expr(col_sum(name), sea_query::Order::Desc);
             }
-            if let Some(v) = filter.lt {
-                qq = qq.and_having(col_sum(name).lt(v));
-            }
-            if let Some(v) = filter.gt {
-                qq = qq.and_having(col_sum(name).gt(v));
-            }
-            if let Some(v) = filter.lte {
-                qq = qq.and_having(col_sum(name).lte(v));
-            }
-            if let Some(v) = filter.gte {
-                qq = qq.and_having(col_sum(name).gte(v));
+
+            for (filter_key, filter) in &q.filters {
+                let name: &'static str = filter_key.into();
+                for expr in filter.exprs(col_sum(name)) {
+                    qq = qq.and_having(expr);
+                }
             }
         }
 
-        let (q, vals) = qq.build_sqlx(PostgresQueryBuilder);
+        let final_query = if let Some(accumulate) = q.accumulate {
+            accumulate_stats_query(qqq, &q, accumulate, &key_columns, &partition_columns)
+        } else {
+            qqq
+        };
+
+        let (q, vals) = final_query.build_sqlx(PostgresQueryBuilder);
 
         tracing::info!("generated query: {}, {:?}", &q, &vals);
         let s = try_stream! {
@@ -614,9 +1330,11 @@ impl ChronDb {
         assert!(event_indexes.len() == event_pitchers.len());
         assert!(event_indexes.len() == event_batters.len());
 
+        self.ensure_game_events_partition(season).await?;
+
         let chunk_size = 10;
         for i in (0..event_indexes.len()).step_by(chunk_size) {
-            sqlx::query("insert into game_events (game_id, index, data, pitcher_id, batter_id, observed_at, season, day) select $1 as game_id, unnest($2::int[]) as index, unnest($3::jsonb[]) as data, unnest($4::text[]) as pitcher_id, unnest($5::text[]) as batter_id, $6 as observed_at, $7 as season, $8 as day on conflict (game_id, index) do update set observed_at = excluded.observed_at, pitcher_id = excluded.pitcher_id, batter_id = excluded.batter_id, season = excluded.season, day = excluded.day where (game_events.observed_at is null or excluded.observed_at <= game_events.observed_at)")
+            sqlx::query("insert into game_events (game_id, index, data, pitcher_id, batter_id, observed_at, season, day) select $1 as game_id, unnest($2::int[]) as index, unnest($3::jsonb[]) as data, unnest($4::text[]) as pitcher_id, unnest($5::text[]) as batter_id, $6 as observed_at, $7 as season, $8 as day on conflict (game_id, index, season) do update set observed_at = excluded.observed_at, pitcher_id = excluded.pitcher_id, batter_id = excluded.batter_id, day = excluded.day where (game_events.observed_at is null or excluded.observed_at <= game_events.observed_at)")
                 .bind(game_id)
                 .bind(&event_indexes[i..(i+chunk_size).min(event_indexes.len())])
                 .bind(&event_datas[i..(i+chunk_size).min(event_indexes.len())])
@@ -631,6 +1349,348 @@ impl ChronDb {
         Ok(())
     }
 
+    pub async fn update_plays(&self, plays: &[DbPlay]) -> anyhow::Result<()> {
+        if plays.is_empty() {
+            return Ok(());
+        }
+
+        // easier than binding 27 separate arrays
+        let json = serde_json::to_value(plays)?;
+        sqlx::query("insert into plays select * from jsonb_populate_recordset(null::plays, $1) on conflict (game_id, index) do update set (season, day, inning, inning_side, event, pitcher_id, batter_id, balls, strikes, outs, on_1b, on_2b, on_3b, home_score, away_score, pitch_result, pitch_type, pitch_speed, pitch_zone, batted_ball, outcome, fielders, advances, outs_recorded, runs_scored) = (excluded.season, excluded.day, excluded.inning, excluded.inning_side, excluded.event, excluded.pitcher_id, excluded.batter_id, excluded.balls, excluded.strikes, excluded.outs, excluded.on_1b, excluded.on_2b, excluded.on_3b, excluded.home_score, excluded.away_score, excluded.pitch_result, excluded.pitch_type, excluded.pitch_speed, excluded.pitch_zone, excluded.batted_ball, excluded.outcome, excluded.fielders, excluded.advances, excluded.outs_recorded, excluded.runs_scored)")
+            .bind(json)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_game_events(
+        &self,
+        game_id: &str,
+        from_index: i32,
+    ) -> anyhow::Result<Vec<DbGameEvent>> {
+        let res = sqlx::query_as("select game_id, index, season, day, data, pitcher_id, batter_id from game_events where game_id = $1 and index >= $2 order by index")
+            .bind(game_id)
+            .bind(from_index)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(res)
+    }
+
+    pub async fn get_game_event(
+        &self,
+        game_id: &str,
+        index: i32,
+    ) -> anyhow::Result<Option<DbGameEvent>> {
+        let res = sqlx::query_as("select game_id, index, season, day, data, pitcher_id, batter_id from game_events where game_id = $1 and index = $2")
+            .bind(game_id)
+            .bind(index)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(res)
+    }
+
+    // plate appearances are any play with a batter outcome, ie. not steals
+    pub async fn get_matchups(&self, q: GetMatchupsQuery) -> anyhow::Result<Vec<MatchupRow>> {
+        let count = |outcomes: &str| {
+            Expr::cust(format!(
+                "(count(*) filter (where outcome in ({})))::int",
+                outcomes
+            ))
+        };
+        let latest_name = |col: &str| {
+            Expr::cust(format!(
+                "(select player_name from player_name_map where player_name_map.player_id = {} order by timestamp desc limit 1)",
+                col
+            ))
+        };
+
+        let mut qq = Query::select()
+            .from(Idens::Plays)
+            .and_where(Expr::col(Idens::Outcome).is_not_null())
+            .and_where(Expr::col(Idens::Outcome).is_not_in(["stolen_base", "caught_stealing"]))
+            .expr_as(Expr::cust("count(*)::int"), "pa")
+            .expr_as(
+                count("'single', 'double', 'triple', 'home_run', 'strikeout', 'ground_out', 'fly_out', 'line_out', 'pop_out', 'force_out', 'fielders_choice', 'double_play', 'triple_play', 'reached_on_error'"),
+                "ab",
+            )
+            .expr_as(count("'single', 'double', 'triple', 'home_run'"), "h")
+            .expr_as(count("'double'"), "doubles")
+            .expr_as(count("'triple'"), "triples")
+            .expr_as(count("'home_run'"), "hr")
+            .expr_as(count("'walk'"), "bb")
+            .expr_as(count("'hit_by_pitch'"), "hbp")
+            .expr_as(count("'strikeout'"), "k")
+            .expr_as(count("'sacrifice_fly'"), "sf")
+            .to_owned();
+
+        if let Some(batter) = &q.batter {
+            qq = qq
+                .and_where(Expr::col(Idens::BatterId).eq(batter))
+                .to_owned();
+        }
+        if let Some(pitcher) = &q.pitcher {
+            qq = qq
+                .and_where(Expr::col(Idens::PitcherId).eq(pitcher))
+                .to_owned();
+        }
+
+        // whichever side wasn't pinned down gets one row per player, unless we're combining
+        for (id_alias, name_alias, pinned) in [
+            ("batter_id", "batter_name", q.batter.is_some()),
+            ("pitcher_id", "pitcher_name", q.pitcher.is_some()),
+        ] {
+            if pinned || !q.combined {
+                qq = qq
+                    .expr_as(Expr::col(Alias::new(id_alias)), id_alias)
+                    .expr_as(latest_name(id_alias), name_alias)
+                    .add_group_by([Expr::col(Alias::new(id_alias)).into()])
+                    .to_owned();
+            } else {
+                qq = qq
+                    .expr_as(Expr::cust("null::text"), id_alias)
+                    .expr_as(Expr::cust("null::text"), name_alias)
+                    .to_owned();
+            }
+        }
+
+        if let Some((s, d)) = q.start {
+            qq = qq
+                .and_where(
+                    Expr::tuple([
+                        Expr::col(Idens::Season).into(),
+                        Expr::col(Idens::Day).into(),
+                    ])
+                    .gte(Expr::tuple([Expr::value(s as i16), Expr::value(d as i16)])),
+                )
+                .to_owned();
+        }
+
+        if let Some((s, d)) = q.end {
+            qq = qq
+                .and_where(
+                    Expr::tuple([
+                        Expr::col(Idens::Season).into(),
+                        Expr::col(Idens::Day).into(),
+                    ])
+                    .lte(Expr::tuple([Expr::value(s as i16), Expr::value(d as i16)])),
+                )
+                .to_owned();
+        }
+
+        qq = qq
+            .order_by_expr(Expr::cust("count(*)"), sea_query::Order::Desc)
+            .to_owned();
+
+        let (q, vals) = qq.build_sqlx(PostgresQueryBuilder);
+        let res = sqlx::query_as_with(&q, vals).fetch_all(&self.pool).await?;
+        Ok(res)
+    }
+
+    pub async fn get_re24_matrix(&self, season: i32) -> anyhow::Result<Vec<Re24MatrixRow>> {
+        let res =
+            sqlx::query_as("select * from re24_matrix where season = $1 order by outs, bases")
+                .bind(season as i16)
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(res)
+    }
+
+    // re24 per plate appearance is RE(after) - RE(before) + runs scored on the play,
+    // pitchers get the negation of what they allowed
+    pub async fn get_re24_leaders(
+        &self,
+        season: i32,
+        pitchers: bool,
+        min_pa: i32,
+        count: i64,
+    ) -> anyhow::Result<Vec<Re24PlayerRow>> {
+        let (player_col, sign) = if pitchers {
+            ("pitcher_id", "-")
+        } else {
+            ("batter_id", "")
+        };
+
+        let res = sqlx::query_as(&format!(
+            r"select
+                s.{player_col} as player_id,
+                (select player_name from player_name_map where player_name_map.player_id = s.{player_col} order by timestamp desc limit 1) as player_name,
+                count(*)::int as pa,
+                ({sign}sum(coalesce(after.run_expectancy, 0) - coalesce(before.run_expectancy, 0) + s.runs_scored))::real as re24
+            from base_out_states s
+            left join re24_matrix before on before.season = s.season and before.outs = s.outs_before and before.bases = s.bases_before
+            left join re24_matrix after on after.season = s.season and after.outs = s.outs_after and after.bases = s.bases_after
+            where s.season = $1 and s.is_pa and s.{player_col} is not null
+            group by s.{player_col}
+            having count(*) >= $2
+            order by re24 desc
+            limit $3",
+        ))
+        .bind(season as i16)
+        .bind(min_pa)
+        .bind(count)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(res)
+    }
+
+    pub async fn get_win_expectancy(&self) -> anyhow::Result<Vec<WinExpectancyRow>> {
+        let res = sqlx::query_as("select inning, inning_side, outs, bases, run_diff, occurrences, home_win_pct from win_expectancy")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(res)
+    }
+
+    // every event counts, so steals and wild pitches land on whoever was batting/pitching
+    pub async fn get_wpa_leaders(
+        &self,
+        season: i32,
+        pitchers: bool,
+        count: i64,
+    ) -> anyhow::Result<Vec<WpaPlayerRow>> {
+        let (player_col, sign) = if pitchers {
+            ("pitcher_id", "-")
+        } else {
+            ("batter_id", "")
+        };
+
+        let res = sqlx::query_as(&format!(
+            r"select
+                {player_col} as player_id,
+                (select player_name from player_name_map where player_name_map.player_id = {player_col} order by timestamp desc limit 1) as player_name,
+                (count(*) filter (where is_pa))::int as pa,
+                ({sign}sum(case when inning_side = 1 then wp_after - wp_before else wp_before - wp_after end))::real as wpa
+            from win_probability_events
+            where season = $1 and {player_col} is not null
+            group by {player_col}
+            order by wpa desc
+            limit $2",
+        ))
+        .bind(season as i16)
+        .bind(count)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(res)
+    }
+
+    pub async fn get_game_plays(&self, game_id: &str) -> anyhow::Result<Vec<DbPlay>> {
+        let res = sqlx::query_as("select * from plays where game_id = $1 order by index")
+            .bind(game_id)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(res)
+    }
+
+    pub async fn get_game_pitches(&self, game_id: &str) -> anyhow::Result<Vec<DbPitch>> {
+        let res = sqlx::query_as("select game_id, index, season, day, inning, inning_side, pitcher_id, batter_id, pa_number, pitch_number, balls_before, strikes_before, balls_after, strikes_after, pitch_result, pitch_type, pitch_speed, pitch_zone, batted_ball, outcome from pitches where game_id = $1 order by index")
+            .bind(game_id)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(res)
+    }
+
+    pub async fn get_pitch_stats(
+        &self,
+        q: GetPitchStatsQuery,
+    ) -> anyhow::Result<Vec<PitchStatsRow>> {
+        let (type_col, type_group) = if q.by_type {
+            ("pitch_type", ", pitch_type")
+        } else {
+            ("null::text", "")
+        };
+
+        let res = sqlx::query_as(&format!(
+            r"select
+                pitcher_id,
+                (select player_name from player_name_map where player_name_map.player_id = pitcher_id order by timestamp desc limit 1) as pitcher_name,
+                {type_col} as pitch_type,
+                count(*)::int as pitches,
+                (count(*) filter (where ends_pa))::int as pa,
+                (count(*)::real / nullif(count(*) filter (where ends_pa), 0))::real as pitches_per_pa,
+                avg(case when pitch_result in ('called_strike', 'swinging_strike', 'foul', 'foul_tip', 'in_play') then 1 else 0 end)::real as strike_rate,
+                avg(case when pitch_result = 'ball' then 1 else 0 end)::real as ball_rate,
+                avg(case when pitch_result = 'called_strike' then 1 else 0 end)::real as called_strike_rate,
+                avg(case when pitch_result = 'swinging_strike' then 1 else 0 end)::real as swinging_strike_rate,
+                avg(case when pitch_result in ('foul', 'foul_tip') then 1 else 0 end)::real as foul_rate,
+                avg(case when pitch_result = 'in_play' then 1 else 0 end)::real as in_play_rate,
+                ((count(*) filter (where pitch_result = 'swinging_strike'))::real
+                    / nullif(count(*) filter (where pitch_result in ('swinging_strike', 'foul', 'foul_tip', 'in_play')), 0))::real as whiff_rate,
+                ((count(*) filter (where pitch_number = 1 and pitch_result <> 'ball' and pitch_result <> 'hit_by_pitch'))::real
+                    / nullif(count(*) filter (where pitch_number = 1), 0))::real as first_pitch_strike_rate,
+                avg(pitch_speed)::real as avg_speed,
+                max(pitch_speed)::real as max_speed
+            from pitches
+            where season = $1 and pitcher_id is not null and ($2::text is null or pitcher_id = $2)
+            group by pitcher_id{type_group}
+            having count(*) >= $3
+            order by pitches desc
+            limit $4",
+        ))
+        .bind(q.season as i16)
+        .bind(q.pitcher)
+        .bind(q.min_pitches)
+        .bind(q.count)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(res)
+    }
+
+    pub async fn get_game(&self, game_id: &str) -> anyhow::Result<Option<DbGame>> {
+        let res = sqlx::query_as("select * from games where game_id = $1")
+            .bind(game_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(res)
+    }
+
+    pub async fn get_game_player_lines(
+        &self,
+        game_id: &str,
+    ) -> anyhow::Result<Vec<GamePlayerLine>> {
+        let mut qq = Query::select()
+            .columns([Idens::TeamId, Idens::PlayerId, Idens::PlayerName])
+            .from(Idens::GamePlayerStatsExploded)
+            .and_where(Expr::col(Idens::GameId).eq(game_id))
+            .to_owned();
+
+        for x in StatKey::VARIANTS {
+            let name: &'static str = x.into();
+            qq = qq
+                .expr_as(
+                    Expr::cust(format!("coalesce({}, 0)", name)).cast_as("int"),
+                    name,
+                )
+                .to_owned();
+        }
+
+        let (q, vals) = qq.build_sqlx(PostgresQueryBuilder);
+        let res = sqlx::query_as_with(&q, vals).fetch_all(&self.pool).await?;
+        Ok(res)
+    }
+
+    pub async fn get_game_half_inning_scores(
+        &self,
+        game_id: &str,
+    ) -> anyhow::Result<Vec<DbHalfInningScore>> {
+        let res = sqlx::query_as(
+            r"select (data->>'inning')::int as inning, (data->>'inning_side')::int as inning_side, max((data->>'away_score')::int) as away_score, max((data->>'home_score')::int) as home_score
+            from game_events
+            where game_id = $1 and data->>'inning' is not null and data->>'inning_side' is not null
+            group by 1, 2
+            order by 1, 2",
+        )
+        .bind(game_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(res)
+    }
+
+    pub async fn get_all_game_ids(&self) -> anyhow::Result<Vec<String>> {
+        let res = sqlx::query_scalar("select game_id from games")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(res)
+    }
+
     pub async fn update_game_player_stats(
         &self,
         game_id: &str,
@@ -857,6 +1917,88 @@ impl ChronDb {
     }
 }
 
+// wraps a per-day stats query into running totals, roughly:
+// select * from (
+//     select <keys>, sum(x) over (partition by season, <group keys> order by day rows between ...) as x
+//     from (<inner>) as daily
+// ) as accumulated where <filters> order by <sort> limit <count>
+fn accumulate_stats_query(
+    inner: SelectStatement,
+    q: &StatsQueryNew,
+    accumulate: StatsAccumulate,
+    key_columns: &[&'static str],
+    partition_columns: &[&'static str],
+) -> SelectStatement {
+    // sea-query's named WINDOW clause doesn't emit the parens postgres wants,
+    // and the cast has to wrap the whole windowed sum anyway, so build it by hand
+    // (all of these are our own column names, nothing user-supplied)
+    let mut partition_columns = partition_columns.to_vec();
+    partition_columns.insert(0, "season");
+
+    let mut window = String::new();
+    window.push_str("partition by ");
+    window.push_str(&partition_columns.join(", "));
+    window.push_str(" order by day");
+    if q.group_game {
+        window.push_str(", game_id");
+    }
+    match accumulate {
+        StatsAccumulate::Cumulative => {
+            window.push_str(" rows between unbounded preceding and current row")
+        }
+        // per game there's one row per game, otherwise go by the day number so days off count too
+        StatsAccumulate::Rolling(n) if q.group_game => window.push_str(&format!(
+            " rows between {} preceding and current row",
+            n.saturating_sub(1)
+        )),
+        StatsAccumulate::Rolling(n) => window.push_str(&format!(
+            " range between {} preceding and current row",
+            n.saturating_sub(1)
+        )),
+    }
+
+    let mut windowed = Query::select();
+    windowed.from_subquery(inner, Alias::new("daily"));
+    for col in key_columns {
+        windowed.column(Alias::new(*col));
+    }
+    for x in &q.fields {
+        let name: &'static str = x.into();
+        windowed.expr_as(
+            Expr::cust(format!("sum({}) over ({})", name, window)).cast_as("int"),
+            name,
+        );
+    }
+
+    let mut outer = Query::select();
+    outer
+        .column(Asterisk)
+        .from_subquery(windowed, Alias::new("accumulated"));
+
+    for (filter_key, filter) in &q.filters {
+        let name: &'static str = filter_key.into();
+        for expr in filter.exprs(Expr::col(Alias::new(name)).into()) {
+            outer.and_where(expr);
+        }
+    }
+
+    if let Some(sort) = q.sort {
+        let name: &'static str = sort.into();
+        outer.order_by(Alias::new(name), sea_query::Order::Desc);
+    } else {
+        outer.order_by_columns([
+            (Idens::Season, sea_query::Order::Asc),
+            (Idens::Day, sea_query::Order::Asc),
+        ]);
+    }
+
+    if let Some(count) = q.count {
+        outer.limit(count);
+    }
+
+    outer
+}
+
 pub struct DbTeamSaveModel<'a> {
     pub team_id: &'a str,
     pub league_id: Option<&'a str>,
@@ -3,7 +3,7 @@ use std::{collections::HashSet, str::FromStr, sync::Arc};
 use anyhow::anyhow;
 use chron_base::ChronConfig;
 use dashmap::DashSet;
-use futures::{StreamExt, stream};
+use futures::{StreamExt, TryStreamExt, stream};
 use itertools::Itertools;
 use models::{EntityKind, NewObject};
 use sea_query::Iden;
@@ -17,18 +17,23 @@ use time::{Duration, OffsetDateTime};
 use tracing::{error, info};
 use util::HashingWriter;
 use uuid::Uuid;
+use versioning::VersionBuilder;
 
 pub mod derived;
 pub mod models;
 pub mod queries;
+pub mod storage;
 pub mod util;
+pub mod versioning;
 
 #[derive(Iden)]
 pub enum Idens {
     AnyValue,
     AwayTeamId,
+    BatterId,
     Data,
     Day,
+    DaySpecial,
     EntityId,
     Event,
     Events,
@@ -44,14 +49,18 @@ pub enum Idens {
     Location,
     Name,
     Objects,
+    Outcome,
     Payload,
+    PitcherId,
     PlayerId,
     PlayerName,
     PlayerNameMap,
     Players,
+    Plays,
     Raw,
     Season,
     Slot,
+    State,
     TeamId,
     Teams,
     Timestamp,
@@ -64,6 +73,8 @@ pub enum Idens {
 pub struct ChronDb {
     pub pool: PgPool,
     pub saved_objects: Arc<DashSet<Uuid>>,
+    // partitions we know exist, so we only ask postgres once per month/season
+    pub partitions: Arc<DashSet<String>>,
 }
 
 impl ChronDb {
@@ -75,6 +86,7 @@ impl ChronDb {
         Ok(ChronDb {
             pool,
             saved_objects: Arc::new(DashSet::new()),
+            partitions: Arc::new(DashSet::new()),
         })
     }
 
@@ -92,6 +104,7 @@ impl ChronDb {
         Ok(ChronDb {
             pool,
             saved_objects: Arc::new(DashSet::new()),
+            partitions: Arc::new(DashSet::new()),
         })
     }
 
@@ -117,12 +130,73 @@ impl ChronDb {
         Ok(())
     }
 
+    // same as the rebuild_entity sql function, but the versions get built in rust
     pub async fn rebuild(&self, kind: EntityKind, entity_id: String) -> anyhow::Result<()> {
-        sqlx::query("select rebuild_entity($1::smallint, $2::text)")
+        let mut tx = self.pool.begin().await?;
+
+        // holding the latest_versions row keeps add_version off this entity until we commit,
+        // so unlike the sql function nobody else ever sees a placeholder here
+        sqlx::query("insert into latest_versions (kind, entity_id, seq, hash, valid_from) values ($1, $2, 0, $3, '9999-01-01T00:00:00Z') on conflict (kind, entity_id) do update set seq = latest_versions.seq")
             .bind(kind)
-            .bind(entity_id)
-            .execute(&self.pool)
+            .bind(&entity_id)
+            .bind(Uuid::nil())
+            .execute(&mut *tx)
+            .await?;
+
+        let mut builder = VersionBuilder::new();
+        let mut versions = Vec::new();
+        {
+            let mut observations = sqlx::query_as::<_, (Uuid, OffsetDateTime)>(
+                "select hash, timestamp from observations where kind = $1 and entity_id = $2 order by timestamp",
+            )
+            .bind(kind)
+            .bind(&entity_id)
+            .fetch(&mut *tx);
+
+            while let Some((hash, timestamp)) = observations.try_next().await? {
+                versions.extend(builder.push(hash, timestamp));
+            }
+        }
+        versions.extend(builder.finish());
+
+        sqlx::query("delete from versions where kind = $1 and entity_id = $2")
+            .bind(kind)
+            .bind(&entity_id)
+            .execute(&mut *tx)
             .await?;
+
+        let Some(latest) = versions.last() else {
+            // no observations at all, so there shouldn't be a latest version either
+            sqlx::query("delete from latest_versions where kind = $1 and entity_id = $2")
+                .bind(kind)
+                .bind(&entity_id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            return Ok(());
+        };
+
+        sqlx::query("update latest_versions set seq = $3, hash = $4, valid_from = $5 where kind = $1 and entity_id = $2")
+            .bind(kind)
+            .bind(&entity_id)
+            .bind(latest.seq)
+            .bind(latest.hash)
+            .bind(latest.valid_from)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("insert into versions (kind, entity_id, seq, hash, valid_from, valid_to, last_seen) select $1, $2, unnest($3::int[]), unnest($4::uuid[]), unnest($5::timestamptz[]), unnest($6::timestamptz[]), unnest($7::timestamptz[])")
+            .bind(kind)
+            .bind(&entity_id)
+            .bind(versions.iter().map(|x| x.seq).collect::<Vec<_>>())
+            .bind(versions.iter().map(|x| x.hash).collect::<Vec<_>>())
+            .bind(versions.iter().map(|x| x.valid_from).collect::<Vec<_>>())
+            .bind(versions.iter().map(|x| x.valid_to).collect::<Vec<_>>())
+            .bind(versions.iter().map(|x| x.last_seen).collect::<Vec<_>>())
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
         Ok(())
     }
 
@@ -221,6 +295,50 @@ impl ChronDb {
         Ok(())
     }
 
+    // observations are partitioned by month with no default partition, so anything inserting
+    // has to make sure the months it touches exist first
+    pub async fn ensure_observation_partitions(
+        &self,
+        timestamps: impl IntoIterator<Item = OffsetDateTime>,
+    ) -> anyhow::Result<()> {
+        let mut months = HashSet::new();
+        for ts in timestamps {
+            let ts = ts.to_offset(time::UtcOffset::UTC);
+            months.insert((ts.year(), ts.month()));
+        }
+
+        for (year, month) in months {
+            let name = format!("observations_{}_{:02}", year, month as u8);
+            if self.partitions.contains(&name) {
+                continue;
+            }
+
+            let month_start = time::Date::from_calendar_date(year, month, 1)?
+                .midnight()
+                .assume_utc();
+            sqlx::query("select ensure_observations_partition($1)")
+                .bind(month_start)
+                .execute(&self.pool)
+                .await?;
+            self.partitions.insert(name);
+        }
+        Ok(())
+    }
+
+    pub async fn ensure_game_events_partition(&self, season: i32) -> anyhow::Result<()> {
+        let name = format!("game_events_{}", season);
+        if self.partitions.contains(&name) {
+            return Ok(());
+        }
+
+        sqlx::query("select ensure_game_events_partition($1)")
+            .bind(season as i16)
+            .execute(&self.pool)
+            .await?;
+        self.partitions.insert(name);
+        Ok(())
+    }
+
     pub async fn insert_observation_raw(
         &self,
         kind: EntityKind,
@@ -229,6 +347,7 @@ impl ChronDb {
         request_time: f64,
         hash: Uuid,
     ) -> anyhow::Result<()> {
+        self.ensure_observation_partitions([timestamp]).await?;
         sqlx::query("insert into observations (kind, entity_id, timestamp, request_time, hash) values ($1, $2, $3, $4, $5)")
             .bind(kind)
             .bind(entity_id)
@@ -243,6 +362,9 @@ impl ChronDb {
         &self,
         observations: &[(EntityKind, std::string::String, OffsetDateTime, f64, Uuid)],
     ) -> anyhow::Result<()> {
+        self.ensure_observation_partitions(observations.iter().map(|x| x.2))
+            .await?;
+
         let kinds = observations.iter().map(|x| x.0).collect::<Vec<_>>();
         let ids = observations.iter().map(|x| x.1.clone()).collect::<Vec<_>>();
         let timestamps = observations.iter().map(|x| x.2).collect::<Vec<_>>();
@@ -311,14 +433,25 @@ impl ChronDb {
         timestamp: OffsetDateTime,
         request_time: Duration,
     ) -> anyhow::Result<()> {
-        sqlx::query("select add_version($1, $2, $3, $4, $5)")
+        // add_version inserts the observation too
+        self.ensure_observation_partitions([timestamp]).await?;
+
+        // add_version only ever appends, anything that isn't newer than what we've already seen
+        // (backfills, mirrors, replays) comes back as -1 and has to be fit in between the
+        // existing versions
+        let res: i32 = sqlx::query_scalar("select add_version($1, $2, $3, $4, $5)")
             .bind(kind)
             .bind(entity_id)
             .bind(hash)
             .bind(timestamp)
             .bind(request_time.as_seconds_f32())
-            .execute(&self.pool)
+            .fetch_one(&self.pool)
             .await?;
+        if res < 0 {
+            return self
+                .add_late_version(kind, entity_id, hash, timestamp, request_time)
+                .await;
+        }
 
         Ok(())
     }
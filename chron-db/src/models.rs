@@ -9,7 +9,7 @@ use uuid::Uuid;
 
 #[repr(i16)]
 #[derive(
-    Debug, Clone, Copy, Type, Deserialize, Serialize, PartialEq, Eq, VariantArray, FromRepr,
+    Debug, Clone, Copy, Type, Deserialize, Serialize, PartialEq, Eq, Hash, VariantArray, FromRepr,
 )]
 #[serde(rename_all = "snake_case")]
 pub enum EntityKind {
@@ -49,6 +49,11 @@ pub struct EntityVersion {
     pub entity_id: String,
     pub valid_from: IsoDateTime,
     pub valid_to: Option<IsoDateTime>,
+    // the last observation before it ended. only filled in by queries that select it, and never
+    // for the latest version
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<IsoDateTime>,
     pub data: sqlx::types::Json<Box<JsonRawValue>>,
 }
 
@@ -132,7 +137,8 @@ impl FromStr for PageToken {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let engine = base64::engine::general_purpose::URL_SAFE;
         let data = engine.decode(s)?;
-        if data.len() <= 16 {
+        // 8 byte timestamp, then the entity id
+        if data.len() <= 8 {
             return Err(anyhow::anyhow!("invalid page token"));
         }
 
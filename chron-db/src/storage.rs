@@ -0,0 +1,371 @@
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use futures::{Stream, StreamExt, TryStreamExt, stream};
+use sqlx::types::{Json, JsonRawValue};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{
+    ChronDb, json_hash,
+    models::{EntityKind, EntityObservationRaw, EntityVersion, NewObject, PageToken},
+    queries::{GetEntitiesQuery, GetVersionsQuery, PaginatedResult, SortOrder, with_page_token},
+    versioning::build_versions,
+};
+
+pub type VersionStream<'a> = Pin<Box<dyn Stream<Item = anyhow::Result<EntityVersion>> + Send + 'a>>;
+
+// the "store observations, get versions back out" part of ChronDb. anything written against this
+// instead of ChronDb directly can run on MemoryStorage without a postgres around, so far that's
+// the entity/version api handlers. workers still hold a ChronDb since most of them also go
+// through the pool for the derived tables
+pub trait Storage: Send + Sync {
+    fn save(&self, obj: NewObject) -> impl Future<Output = anyhow::Result<()>> + Send;
+
+    // the object for `hash` has to already be saved
+    fn add_version(
+        &self,
+        kind: EntityKind,
+        entity_id: &str,
+        hash: Uuid,
+        timestamp: OffsetDateTime,
+        request_time: Duration,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send;
+
+    fn get_entities(
+        &self,
+        q: GetEntitiesQuery,
+    ) -> impl Future<Output = anyhow::Result<PaginatedResult<EntityVersion>>> + Send;
+
+    fn get_versions(
+        &self,
+        q: GetVersionsQuery,
+    ) -> impl Future<Output = anyhow::Result<PaginatedResult<EntityVersion>>> + Send;
+
+    fn get_all_latest_stream(&self, kind: EntityKind) -> VersionStream<'_>;
+
+    fn get_versions_stream(&self, kind: EntityKind, entity_id: &str) -> VersionStream<'_>;
+}
+
+impl Storage for ChronDb {
+    async fn save(&self, obj: NewObject) -> anyhow::Result<()> {
+        ChronDb::save(self, obj).await
+    }
+
+    async fn add_version(
+        &self,
+        kind: EntityKind,
+        entity_id: &str,
+        hash: Uuid,
+        timestamp: OffsetDateTime,
+        request_time: Duration,
+    ) -> anyhow::Result<()> {
+        ChronDb::add_version(self, kind, entity_id, hash, timestamp, request_time).await
+    }
+
+    async fn get_entities(
+        &self,
+        q: GetEntitiesQuery,
+    ) -> anyhow::Result<PaginatedResult<EntityVersion>> {
+        ChronDb::get_entities(self, q).await
+    }
+
+    async fn get_versions(
+        &self,
+        q: GetVersionsQuery,
+    ) -> anyhow::Result<PaginatedResult<EntityVersion>> {
+        ChronDb::get_versions(self, q).await
+    }
+
+    fn get_all_latest_stream(&self, kind: EntityKind) -> VersionStream<'_> {
+        ChronDb::get_all_latest_stream(self, kind)
+    }
+
+    fn get_versions_stream(&self, kind: EntityKind, entity_id: &str) -> VersionStream<'_> {
+        Box::pin(ChronDb::get_versions_stream(self, kind, entity_id).map_err(anyhow::Error::new))
+    }
+}
+
+// same versioning rules as ChronDb, just kept in a few maps. cloning shares the same data
+#[derive(Clone, Default)]
+pub struct MemoryStorage {
+    state: Arc<Mutex<MemoryState>>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    objects: HashMap<Uuid, Box<JsonRawValue>>,
+    observations: Vec<EntityObservationRaw>,
+    versions: Vec<MemoryVersion>,
+    latest: HashMap<(EntityKind, String), MemoryVersion>,
+}
+
+#[derive(Clone)]
+struct MemoryVersion {
+    kind: EntityKind,
+    entity_id: String,
+    seq: i32,
+    hash: Uuid,
+    valid_from: OffsetDateTime,
+    valid_to: Option<OffsetDateTime>,
+    last_seen: Option<OffsetDateTime>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+
+    pub fn observations(&self, kind: EntityKind, entity_id: &str) -> Vec<EntityObservationRaw> {
+        let state = self.state.lock().unwrap();
+        state
+            .observations
+            .iter()
+            .filter(|x| x.kind == kind && x.entity_id == entity_id)
+            .cloned()
+            .collect()
+    }
+
+    fn to_entity_version(state: &MemoryState, v: &MemoryVersion) -> Option<EntityVersion> {
+        let data = state.objects.get(&v.hash)?;
+        Some(EntityVersion {
+            kind: v.kind,
+            entity_id: v.entity_id.clone(),
+            valid_from: v.valid_from.into(),
+            valid_to: v.valid_to.map(Into::into),
+            last_seen: v.last_seen.map(Into::into),
+            data: Json(data.clone()),
+        })
+    }
+
+    fn query_versions(
+        &self,
+        kind: EntityKind,
+        ids: &[String],
+        filter: impl Fn(&MemoryVersion) -> bool,
+        order: SortOrder,
+        page: Option<PageToken>,
+        count: u64,
+    ) -> PaginatedResult<EntityVersion> {
+        let state = self.state.lock().unwrap();
+        let mut matching = state
+            .versions
+            .iter()
+            .filter(|v| v.kind == kind)
+            .filter(|v| ids.is_empty() || ids.contains(&v.entity_id))
+            .filter(|v| filter(v))
+            .filter(|v| match &page {
+                Some(page) => {
+                    let key = (v.valid_from, v.entity_id.as_str());
+                    let page_key = (page.timestamp, page.entity_id.as_str());
+                    match order {
+                        SortOrder::Asc => key > page_key,
+                        SortOrder::Desc => key < page_key,
+                    }
+                }
+                None => true,
+            })
+            .collect::<Vec<_>>();
+
+        matching.sort_by(|a, b| {
+            let ord = (a.valid_from, &a.entity_id).cmp(&(b.valid_from, &b.entity_id));
+            match order {
+                SortOrder::Asc => ord,
+                SortOrder::Desc => ord.reverse(),
+            }
+        });
+
+        let items = matching
+            .into_iter()
+            .take(count as usize)
+            .filter_map(|v| Self::to_entity_version(&state, v))
+            .collect();
+        with_page_token(items)
+    }
+}
+
+impl Storage for MemoryStorage {
+    async fn save(&self, obj: NewObject) -> anyhow::Result<()> {
+        let (hash, data) = json_hash(obj.data)?;
+        let data = serde_json::value::to_raw_value(&data)?;
+        self.state
+            .lock()
+            .unwrap()
+            .objects
+            .entry(hash)
+            .or_insert(data);
+
+        self.add_version(
+            obj.kind,
+            &obj.entity_id,
+            hash,
+            obj.timestamp,
+            obj.request_time,
+        )
+        .await
+    }
+
+    async fn add_version(
+        &self,
+        kind: EntityKind,
+        entity_id: &str,
+        hash: Uuid,
+        timestamp: OffsetDateTime,
+        request_time: Duration,
+    ) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        if !state.objects.contains_key(&hash) {
+            return Err(anyhow::anyhow!("object {} isn't saved", hash));
+        }
+
+        let key = (kind, entity_id.to_string());
+        let last_timestamp = state
+            .observations
+            .iter()
+            .filter(|o| o.kind == kind && o.entity_id == entity_id)
+            .map(|o| o.timestamp)
+            .max();
+        let late =
+            state.latest.contains_key(&key) && last_timestamp.is_some_and(|x| timestamp <= x);
+
+        state.observations.push(EntityObservationRaw {
+            kind,
+            entity_id: entity_id.to_string(),
+            timestamp,
+            hash,
+            request_time: request_time.as_seconds_f64(),
+        });
+
+        if late {
+            // ChronDb only rebuilds the versions around it, but it comes out the same
+            let mut observations = state
+                .observations
+                .iter()
+                .filter(|o| o.kind == kind && o.entity_id == entity_id)
+                .map(|o| (o.hash, o.timestamp))
+                .collect::<Vec<_>>();
+            observations.sort_by_key(|o| o.1);
+
+            let versions = build_versions(observations)
+                .into_iter()
+                .map(|v| MemoryVersion {
+                    kind,
+                    entity_id: entity_id.to_string(),
+                    seq: v.seq,
+                    hash: v.hash,
+                    valid_from: v.valid_from,
+                    valid_to: v.valid_to,
+                    last_seen: v.last_seen,
+                })
+                .collect::<Vec<_>>();
+            state
+                .versions
+                .retain(|v| !(v.kind == kind && v.entity_id == entity_id));
+            if let Some(latest) = versions.last() {
+                state.latest.insert(key, latest.clone());
+            }
+            state.versions.extend(versions);
+            return Ok(());
+        }
+
+        let next_seq = match state.latest.get(&key) {
+            None => Some(0),
+            // only if the hash is different and the timestamp is newer
+            Some(lv) if lv.hash != hash && lv.valid_from < timestamp => Some(lv.seq + 1),
+            Some(_) => None,
+        };
+
+        if let Some(seq) = next_seq {
+            // close off the old one
+            if let Some(prev) = state
+                .versions
+                .iter_mut()
+                .find(|v| v.kind == kind && v.entity_id == entity_id && v.seq == seq - 1)
+            {
+                prev.valid_to = Some(timestamp);
+                prev.last_seen = last_timestamp;
+            }
+
+            let version = MemoryVersion {
+                kind,
+                entity_id: entity_id.to_string(),
+                seq,
+                hash,
+                valid_from: timestamp,
+                valid_to: None,
+                last_seen: None,
+            };
+            state.versions.push(version.clone());
+            state.latest.insert(key, version);
+        }
+        Ok(())
+    }
+
+    async fn get_entities(
+        &self,
+        q: GetEntitiesQuery,
+    ) -> anyhow::Result<PaginatedResult<EntityVersion>> {
+        let filter = |v: &MemoryVersion| {
+            let in_range = match q.at {
+                Some(at) => at >= v.valid_from && v.valid_to.is_none_or(|to| at < to),
+                None => v.valid_to.is_none(),
+            };
+            in_range
+                && q.before.is_none_or(|before| v.valid_from <= before)
+                && q.after.is_none_or(|after| v.valid_from >= after)
+        };
+        Ok(self.query_versions(q.kind, &q.id, filter, q.order, q.page, q.count))
+    }
+
+    async fn get_versions(
+        &self,
+        q: GetVersionsQuery,
+    ) -> anyhow::Result<PaginatedResult<EntityVersion>> {
+        let filter = |v: &MemoryVersion| {
+            q.before.is_none_or(|before| v.valid_from <= before)
+                && q.after.is_none_or(|after| v.valid_from >= after)
+        };
+        Ok(self.query_versions(q.kind, &q.id, filter, q.order, q.page, q.count))
+    }
+
+    fn get_all_latest_stream(&self, kind: EntityKind) -> VersionStream<'_> {
+        let state = self.state.lock().unwrap();
+        let items = state
+            .latest
+            .values()
+            .filter(|v| v.kind == kind)
+            .filter_map(|v| {
+                // latest_versions doesn't know when it ends
+                let v = MemoryVersion {
+                    valid_to: None,
+                    last_seen: None,
+                    ..v.clone()
+                };
+                Self::to_entity_version(&state, &v)
+            })
+            .map(Ok)
+            .collect::<Vec<_>>();
+        stream::iter(items).boxed()
+    }
+
+    fn get_versions_stream(&self, kind: EntityKind, entity_id: &str) -> VersionStream<'_> {
+        let state = self.state.lock().unwrap();
+        let mut versions = state
+            .versions
+            .iter()
+            .filter(|v| v.kind == kind && v.entity_id == entity_id)
+            .collect::<Vec<_>>();
+        versions.sort_by_key(|v| v.valid_from);
+
+        let items = versions
+            .into_iter()
+            .filter_map(|v| Self::to_entity_version(&state, v))
+            .map(Ok)
+            .collect::<Vec<_>>();
+        stream::iter(items).boxed()
+    }
+}
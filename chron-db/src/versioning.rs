@@ -0,0 +1,288 @@
+use futures::TryStreamExt;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{ChronDb, models::EntityKind};
+
+// a `versions` row without the kind/entity_id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionRange {
+    pub seq: i32,
+    pub hash: Uuid,
+    pub valid_from: OffsetDateTime,
+    pub valid_to: Option<OffsetDateTime>,
+    pub last_seen: Option<OffsetDateTime>,
+}
+
+// the loop from the rebuild_entity sql function. feed it observations in timestamp order and a new
+// version starts every time the hash changes. a version is valid until the next one starts and
+// last_seen is the last observation before that. the latest version has neither
+#[derive(Default)]
+pub struct VersionBuilder {
+    current: Option<(Uuid, OffsetDateTime)>,
+    last_timestamp: Option<OffsetDateTime>,
+    next_seq: i32,
+}
+
+impl VersionBuilder {
+    pub fn new() -> VersionBuilder {
+        VersionBuilder::default()
+    }
+
+    // returns the previous version if this observation ended it
+    pub fn push(&mut self, hash: Uuid, timestamp: OffsetDateTime) -> Option<VersionRange> {
+        let mut closed = None;
+        match self.current {
+            None => self.current = Some((hash, timestamp)),
+            Some((current_hash, valid_from)) if current_hash != hash => {
+                closed = Some(VersionRange {
+                    seq: self.next_seq,
+                    hash: current_hash,
+                    valid_from,
+                    valid_to: Some(timestamp),
+                    last_seen: self.last_timestamp,
+                });
+                self.current = Some((hash, timestamp));
+                self.next_seq += 1;
+            }
+            Some(_) => {}
+        }
+
+        self.last_timestamp = Some(timestamp);
+        closed
+    }
+
+    // for when there's already a version starting at `next`, closes the last one off against it
+    pub fn finish_before(self, next: OffsetDateTime) -> Option<VersionRange> {
+        let (hash, valid_from) = self.current?;
+        Some(VersionRange {
+            seq: self.next_seq,
+            hash,
+            valid_from,
+            valid_to: Some(next),
+            last_seen: self.last_timestamp,
+        })
+    }
+
+    // the still-open latest version, if there were any observations at all
+    pub fn finish(self) -> Option<VersionRange> {
+        let (hash, valid_from) = self.current?;
+        Some(VersionRange {
+            seq: self.next_seq,
+            hash,
+            valid_from,
+            valid_to: None,
+            last_seen: None,
+        })
+    }
+}
+
+pub fn build_versions(
+    observations: impl IntoIterator<Item = (Uuid, OffsetDateTime)>,
+) -> Vec<VersionRange> {
+    let mut builder = VersionBuilder::new();
+    let mut versions = observations
+        .into_iter()
+        .filter_map(|(hash, timestamp)| builder.push(hash, timestamp))
+        .collect::<Vec<_>>();
+    versions.extend(builder.finish());
+    versions
+}
+
+impl ChronDb {
+    // for an observation at or before the newest one we have, which the add_version sql function
+    // hands back instead of appending.
+    // only the version it lands in and its neighbours get rebuilt from observations, which splits
+    // that version if it's in the middle of one, or merges it into a neighbour with the same hash.
+    // everything after keeps its ranges and just gets its seq shifted.
+    // anything from before compaction's cutoff gets turned away, see compaction_progress
+    pub(crate) async fn add_late_version(
+        &self,
+        kind: EntityKind,
+        entity_id: &str,
+        hash: Uuid,
+        timestamp: OffsetDateTime,
+        request_time: Duration,
+    ) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        // same row add_version and rebuild take, so the versions can't move under us
+        let latest: Option<(i32, Uuid)> = sqlx::query_as(
+            "select seq, hash from latest_versions where kind = $1 and entity_id = $2 for update",
+        )
+        .bind(kind)
+        .bind(entity_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let latest_seq = match latest {
+            Some((seq, latest_hash)) if !latest_hash.is_nil() => seq,
+            // no versions to fit it into yet (or the sql rebuild_entity has it "locked"),
+            // so the usual rules are fine
+            _ => {
+                sqlx::query("select add_version($1, $2, $3, $4, $5)")
+                    .bind(kind)
+                    .bind(entity_id)
+                    .bind(hash)
+                    .bind(timestamp)
+                    .bind(request_time.as_seconds_f32())
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await?;
+                return Ok(());
+            }
+        };
+
+        // compaction only keeps the ends of each run, so there's nothing to tell where in the run
+        // this one goes anymore, and splitting at the kept ends would stretch it over the gap
+        let compacted_before: Option<OffsetDateTime> =
+            sqlx::query_scalar("select compacted_before from compaction_progress where kind = $1")
+                .bind(kind)
+                .fetch_optional(&mut *tx)
+                .await?;
+        if let Some(compacted_before) = compacted_before.filter(|x| timestamp < *x) {
+            return Err(anyhow::anyhow!(
+                "late observation for {:?} {} at {} is from before compaction's cutoff ({})",
+                kind,
+                entity_id,
+                timestamp,
+                compacted_before
+            ));
+        }
+
+        sqlx::query("insert into observations (kind, entity_id, timestamp, request_time, hash) values ($1, $2, $3, $4, $5)")
+            .bind(kind)
+            .bind(entity_id)
+            .bind(timestamp)
+            .bind(request_time.as_seconds_f64())
+            .bind(hash)
+            .execute(&mut *tx)
+            .await?;
+
+        let containing: Option<i32> = sqlx::query_scalar(
+            "select seq from versions where kind = $1 and entity_id = $2 and valid_from <= $3 order by seq desc limit 1",
+        )
+        .bind(kind)
+        .bind(entity_id)
+        .bind(timestamp)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        // the new observation can only change the version it's in, and only merge with the ones
+        // right next to it. if it's older than everything it goes in front of the first version
+        let (start_seq, end_seq) = match containing {
+            Some(seq) => ((seq - 1).max(0), (seq + 1).min(latest_seq)),
+            None => (0, 0),
+        };
+        let old: Vec<(OffsetDateTime, Option<OffsetDateTime>)> = sqlx::query_as(
+            "select valid_from, valid_to from versions where kind = $1 and entity_id = $2 and seq between $3 and $4 order by seq",
+        )
+        .bind(kind)
+        .bind(entity_id)
+        .bind(start_seq)
+        .bind(end_seq)
+        .fetch_all(&mut *tx)
+        .await?;
+        let (Some(first), Some(last)) = (old.first(), old.last()) else {
+            return Err(anyhow::anyhow!(
+                "{:?} {} has a latest version but no versions, rebuild it",
+                kind,
+                entity_id
+            ));
+        };
+        let window_start = match containing {
+            Some(_) => first.0,
+            None => timestamp,
+        };
+        let window_end = last.1;
+
+        let mut builder = VersionBuilder::new();
+        let mut versions = Vec::new();
+        {
+            let mut observations = sqlx::query_as::<_, (Uuid, OffsetDateTime)>(
+                "select hash, timestamp from observations where kind = $1 and entity_id = $2 and timestamp >= $3 and ($4::timestamptz is null or timestamp < $4) order by timestamp",
+            )
+            .bind(kind)
+            .bind(entity_id)
+            .bind(window_start)
+            .bind(window_end)
+            .fetch(&mut *tx);
+
+            while let Some((hash, timestamp)) = observations.try_next().await? {
+                versions.extend(builder.push(hash, timestamp));
+            }
+        }
+        versions.extend(match window_end {
+            Some(next) => builder.finish_before(next),
+            None => builder.finish(),
+        });
+        for version in &mut versions {
+            version.seq += start_seq;
+        }
+
+        sqlx::query(
+            "delete from versions where kind = $1 and entity_id = $2 and seq between $3 and $4",
+        )
+        .bind(kind)
+        .bind(entity_id)
+        .bind(start_seq)
+        .bind(end_seq)
+        .execute(&mut *tx)
+        .await?;
+
+        let shift = versions.len() as i32 - (end_seq - start_seq + 1);
+        if shift != 0 {
+            // seq is in the primary key, so go through negative numbers to not trip over
+            // ourselves halfway through the update
+            sqlx::query("update versions set seq = -seq - 1 where kind = $1 and entity_id = $2 and seq > $3")
+                .bind(kind)
+                .bind(entity_id)
+                .bind(end_seq)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("update versions set seq = -seq - 1 + $3 where kind = $1 and entity_id = $2 and seq < 0")
+                .bind(kind)
+                .bind(entity_id)
+                .bind(shift)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        sqlx::query("insert into versions (kind, entity_id, seq, hash, valid_from, valid_to, last_seen) select $1, $2, unnest($3::int[]), unnest($4::uuid[]), unnest($5::timestamptz[]), unnest($6::timestamptz[]), unnest($7::timestamptz[])")
+            .bind(kind)
+            .bind(entity_id)
+            .bind(versions.iter().map(|x| x.seq).collect::<Vec<_>>())
+            .bind(versions.iter().map(|x| x.hash).collect::<Vec<_>>())
+            .bind(versions.iter().map(|x| x.valid_from).collect::<Vec<_>>())
+            .bind(versions.iter().map(|x| x.valid_to).collect::<Vec<_>>())
+            .bind(versions.iter().map(|x| x.last_seen).collect::<Vec<_>>())
+            .execute(&mut *tx)
+            .await?;
+
+        match versions.last() {
+            Some(latest) if end_seq == latest_seq => {
+                sqlx::query("update latest_versions set seq = $3, hash = $4, valid_from = $5 where kind = $1 and entity_id = $2")
+                    .bind(kind)
+                    .bind(entity_id)
+                    .bind(latest.seq)
+                    .bind(latest.hash)
+                    .bind(latest.valid_from)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            _ => {
+                sqlx::query(
+                    "update latest_versions set seq = seq + $3 where kind = $1 and entity_id = $2",
+                )
+                .bind(kind)
+                .bind(entity_id)
+                .bind(shift)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
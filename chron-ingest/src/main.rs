@@ -6,16 +6,19 @@ use http::DataClient;
 use tracing::{error, info};
 use uuid::Uuid;
 use workers::{
-    IntervalWorker, SimState, WorkerContext,
+    IntervalWorker, SimState, WorkerContext, archive, compaction, export,
     games::{self},
     league::{self},
-    maintenance,
+    maintenance, mirror, verify,
 };
 
 use crate::workers::{
+    compaction::CompactObservations,
+    export::ExportParquet,
     feeds::{PollPlayerFeeds, PollTeamFeeds, ProcessFeeds},
     games::HandleSuperstarGames,
     league::PollBenches,
+    maintenance::CreatePartitions,
 };
 use crate::workers::{
     games::{HandleEventGames, PollGameDays, PollLiveGames},
@@ -23,11 +26,13 @@ use crate::workers::{
     map::LookupMapLocations,
     matviews::RefreshMatviews,
     message::PollMessage,
+    mirror::FollowMirror,
     misc::PollMiscData,
 };
 
 mod http;
 mod models;
+mod plays;
 mod synthetic;
 mod workers;
 
@@ -102,6 +107,7 @@ async fn main() -> anyhow::Result<()> {
         spawn(ctx.clone(), PollNewPlayers);
         spawn(ctx.clone(), PollBenches);
         spawn(ctx.clone(), RefreshMatviews);
+        spawn(ctx.clone(), CreatePartitions);
         spawn(ctx.clone(), PollMessage);
         spawn(ctx.clone(), PollGameDays);
         spawn(ctx.clone(), PollLiveGames);
@@ -113,6 +119,15 @@ async fn main() -> anyhow::Result<()> {
         spawn(ctx.clone(), ProcessFeeds);
         spawn(ctx.clone(), PollTeamFeeds);
         spawn(ctx.clone(), PollPlayerFeeds);
+        if ctx.config.export_path.is_some() {
+            spawn(ctx.clone(), ExportParquet);
+        }
+        if ctx.config.mirror_url.is_some() {
+            spawn(ctx.clone(), FollowMirror);
+        }
+        if ctx.config.compaction.enabled {
+            spawn(ctx.clone(), CompactObservations);
+        }
 
         stop_signal().await?;
         info!("got ctrl-c, exiting");
@@ -125,8 +140,19 @@ async fn handle_fn(ctx: &WorkerContext, name: &str, args: &[String]) -> anyhow::
         "rebuild-games" => games::rebuild_games(ctx, false).await?,
         "rebuild-games-stats" => games::rebuild_games(ctx, true).await?,
         "rebuild-games-slow" => games::rebuild_games_slow(ctx).await?,
+        "rebuild-plays" => games::rebuild_plays(ctx).await?,
         "rebuild-all" => maintenance::rebuild_all(ctx).await?,
         "recompress" => maintenance::recompress(ctx).await?,
+        "create-partitions" => maintenance::create_partitions(ctx).await?,
+        "compact" => compaction::compact_observations(ctx, false).await?,
+        "compact-dry-run" => compaction::compact_observations(ctx, true).await?,
+        "export" => export::export_all(ctx).await?,
+        "export-archive" => archive::export_archive(ctx, args).await?,
+        "import-archive" => archive::import_archive(ctx, args).await?,
+        "mirror" => mirror::mirror_all(ctx).await?,
+        "mirror-follow" => mirror::follow_mirror(ctx).await?,
+        "verify" => verify::verify(ctx, false, None).await?,
+        "verify-repair" => verify::verify(ctx, true, args.first()).await?,
         "fetch-league" => league::poll_league(ctx).await?,
         "fetch-all-seasons" => games::fetch_all_seasons(ctx).await?,
         "fetch-all-games" => games::fetch_all_games(ctx).await?,
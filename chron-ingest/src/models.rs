@@ -147,6 +147,30 @@ pub struct MmolbGameEvent {
     pub batter: Option<String>,
     pub inning: i32,
     pub inning_side: i32,
+
+    // everything below is only used by the play parser, so be lenient about it
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub balls: Option<i32>,
+    #[serde(default)]
+    pub strikes: Option<i32>,
+    #[serde(default)]
+    pub outs: Option<i32>,
+    #[serde(default)]
+    pub home_score: Option<i32>,
+    #[serde(default)]
+    pub away_score: Option<i32>,
+    #[serde(default)]
+    pub on_1b: Option<bool>,
+    #[serde(default)]
+    pub on_2b: Option<bool>,
+    #[serde(default)]
+    pub on_3b: Option<bool>,
+    #[serde(default)]
+    pub pitch_info: Option<String>, // eg. "95.1 MPH Fastball"
+    #[serde(default)]
+    pub zone: Option<serde_json::Value>, // sometimes a number, sometimes a string, sometimes ""
 }
 
 #[derive(Debug, Deserialize)]
@@ -0,0 +1,644 @@
+use chron_db::derived::DbPlay;
+use serde::Serialize;
+use strum::IntoStaticStr;
+
+use crate::models::MmolbGameEvent;
+
+// best-effort parsing of the event log text into something queryable.
+// the game only gives us prose for most of this, so anything we don't recognize
+// is left as None rather than guessed at
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum PitchResult {
+    Ball,
+    CalledStrike,
+    SwingingStrike,
+    Foul,
+    FoulTip,
+    HitByPitch,
+    InPlay,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum BattedBall {
+    GroundBall,
+    LineDrive,
+    FlyBall,
+    Popup,
+    Bunt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum PlayOutcome {
+    Walk,
+    Strikeout,
+    HitByPitch,
+    Single,
+    Double,
+    Triple,
+    HomeRun,
+    GroundOut,
+    FlyOut,
+    LineOut,
+    PopOut,
+    ForceOut,
+    FieldersChoice,
+    DoublePlay,
+    TriplePlay,
+    SacrificeFly,
+    SacrificeBunt,
+    ReachedOnError,
+    StolenBase,
+    CaughtStealing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Base {
+    First,
+    Second,
+    Third,
+    Home,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunnerAdvance {
+    pub runner: String,
+    pub to: Base,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedPlay {
+    pub pitch_result: Option<PitchResult>,
+    pub pitch_type: Option<String>,
+    pub pitch_speed: Option<f32>,
+    pub pitch_zone: Option<i32>,
+    pub batted_ball: Option<BattedBall>,
+    pub outcome: Option<PlayOutcome>,
+    pub fielders: Vec<&'static str>,
+    pub advances: Vec<RunnerAdvance>,
+    pub outs_recorded: i32,
+    pub runs_scored: i32,
+}
+
+// long names first so "first baseman" doesn't get eaten by anything shorter
+const FIELDER_NAMES: &[(&str, &str)] = &[
+    ("first baseman", "1B"),
+    ("second baseman", "2B"),
+    ("third baseman", "3B"),
+    ("shortstop", "SS"),
+    ("left fielder", "LF"),
+    ("center fielder", "CF"),
+    ("right fielder", "RF"),
+    ("catcher", "C"),
+    ("pitcher", "P"),
+];
+
+const FIELDER_ABBREVIATIONS: &[&str] = &["1B", "2B", "3B", "SS", "LF", "CF", "RF"];
+
+// `prev` is the event right before this one in the same game, if we have it.
+// scores are taken from the state diff when possible since that's exact
+pub fn parse_play(event: &MmolbGameEvent, prev: Option<&MmolbGameEvent>) -> ParsedPlay {
+    let message = strip_tags(event.message.as_deref().unwrap_or(""));
+    let sentences = split_sentences(&message);
+    let lower = message.to_lowercase();
+
+    let mut play = ParsedPlay::default();
+
+    let pitch_info = event.pitch_info.as_deref().unwrap_or("");
+    if let Some((speed, kind)) = pitch_info.split_once(" MPH ") {
+        play.pitch_speed = speed.trim().parse().ok();
+        play.pitch_type = Some(kind.trim().to_string());
+    }
+    play.pitch_zone = match &event.zone {
+        Some(serde_json::Value::Number(n)) => n.as_i64().map(|x| x as i32),
+        Some(serde_json::Value::String(s)) => s.trim().parse().ok(),
+        _ => None,
+    };
+
+    let lower_sentences = sentences
+        .iter()
+        .map(|x| x.to_lowercase())
+        .collect::<Vec<_>>();
+    play.outcome = parse_outcome(&lower_sentences);
+    play.batted_ball = parse_batted_ball(&lower);
+    play.pitch_result = parse_pitch_result(sentences.first().copied().unwrap_or(""), &lower).or(
+        match play.outcome {
+            Some(
+                PlayOutcome::Single
+                | PlayOutcome::Double
+                | PlayOutcome::Triple
+                | PlayOutcome::HomeRun
+                | PlayOutcome::GroundOut
+                | PlayOutcome::FlyOut
+                | PlayOutcome::LineOut
+                | PlayOutcome::PopOut
+                | PlayOutcome::ForceOut
+                | PlayOutcome::FieldersChoice
+                | PlayOutcome::DoublePlay
+                | PlayOutcome::TriplePlay
+                | PlayOutcome::SacrificeFly
+                | PlayOutcome::SacrificeBunt
+                | PlayOutcome::ReachedOnError,
+            ) => Some(PitchResult::InPlay),
+            _ => None,
+        },
+    );
+    play.fielders = parse_fielders(&message);
+
+    if let (Some(batter), Some(outcome)) = (event.batter.as_ref(), play.outcome) {
+        let to = match outcome {
+            PlayOutcome::Walk | PlayOutcome::HitByPitch | PlayOutcome::Single => Some(Base::First),
+            PlayOutcome::Double => Some(Base::Second),
+            PlayOutcome::Triple => Some(Base::Third),
+            PlayOutcome::HomeRun => Some(Base::Home),
+            _ => None,
+        };
+        if let Some(to) = to {
+            play.advances.push(RunnerAdvance {
+                runner: batter.clone(),
+                to,
+            });
+        }
+    }
+
+    let mut text_runs = 0;
+    for sentence in &sentences {
+        if let Some(advance) = parse_advance(sentence) {
+            // batter advances get mentioned in prose too sometimes
+            let seen = play
+                .advances
+                .iter()
+                .any(|x| x.runner == advance.runner && x.to == advance.to);
+            if !seen {
+                if advance.to == Base::Home {
+                    text_runs += 1;
+                }
+                play.advances.push(advance);
+            }
+        }
+    }
+
+    let runner_outs = lower_sentences.iter().filter(|x| runner_out(x)).count() as i32;
+    play.outs_recorded = match play.outcome {
+        // the "out at" sentences are spelling out the same outs again
+        Some(
+            PlayOutcome::ForceOut
+            | PlayOutcome::FieldersChoice
+            | PlayOutcome::DoublePlay
+            | PlayOutcome::TriplePlay,
+        ) => outcome_outs(play.outcome).max(runner_outs),
+        _ => outcome_outs(play.outcome) + runner_outs,
+    };
+    if play.outcome == Some(PlayOutcome::HomeRun) {
+        text_runs = if lower.contains("grand slam") {
+            4
+        } else {
+            text_runs + 1
+        };
+    }
+
+    play.runs_scored = match (prev, total_score(event)) {
+        (Some(prev), Some(now)) => total_score(prev)
+            .map(|before| (now - before).max(0))
+            .unwrap_or(text_runs),
+        _ => text_runs,
+    };
+
+    play
+}
+
+pub fn to_db_play(
+    game_id: &str,
+    index: i32,
+    season: i32,
+    day: i32,
+    event: &MmolbGameEvent,
+    play: ParsedPlay,
+) -> DbPlay {
+    DbPlay {
+        game_id: game_id.to_string(),
+        index,
+        season: season as i16,
+        day: day as i16,
+        inning: event.inning,
+        inning_side: event.inning_side,
+        event: event.event.clone(),
+        // filled in by the caller, which knows the rosters
+        pitcher_id: None,
+        batter_id: None,
+        balls: event.balls,
+        strikes: event.strikes,
+        outs: event.outs,
+        on_1b: event.on_1b,
+        on_2b: event.on_2b,
+        on_3b: event.on_3b,
+        home_score: event.home_score,
+        away_score: event.away_score,
+        pitch_result: play.pitch_result.map(|x| <&str>::from(x).to_string()),
+        pitch_type: play.pitch_type,
+        pitch_speed: play.pitch_speed,
+        pitch_zone: play.pitch_zone,
+        batted_ball: play.batted_ball.map(|x| <&str>::from(x).to_string()),
+        outcome: play.outcome.map(|x| <&str>::from(x).to_string()),
+        fielders: play.fielders.into_iter().map(|x| x.to_string()).collect(),
+        advances: serde_json::to_value(&play.advances).unwrap_or_default(),
+        outs_recorded: play.outs_recorded,
+        runs_scored: play.runs_scored,
+    }
+}
+
+fn total_score(event: &MmolbGameEvent) -> Option<i32> {
+    Some(event.home_score? + event.away_score?)
+}
+
+fn strip_tags(message: &str) -> String {
+    let mut out = String::with_capacity(message.len());
+    let mut in_tag = false;
+    for c in message.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+// splits on . and ! followed by whitespace (or the end), keeping the text without the punctuation
+fn split_sentences(message: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = message.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let at_boundary = chars
+            .peek()
+            .map(|(_, next)| next.is_whitespace())
+            .unwrap_or(true);
+        if (c == '.' || c == '!') && at_boundary {
+            let sentence = message[start..i].trim();
+            if !sentence.is_empty() {
+                sentences.push(sentence);
+            }
+            start = i + c.len_utf8();
+        }
+    }
+    let rest = message[start..].trim();
+    if !rest.is_empty() {
+        sentences.push(rest);
+    }
+    sentences
+}
+
+fn parse_pitch_result(first_sentence: &str, lower: &str) -> Option<PitchResult> {
+    let first = first_sentence.to_lowercase();
+    if lower.contains("hit by the pitch") || lower.contains("hit by a pitch") {
+        Some(PitchResult::HitByPitch)
+    } else if first.starts_with("ball") {
+        Some(PitchResult::Ball)
+    } else if first.starts_with("strike, looking") || lower.contains("struck out looking") {
+        Some(PitchResult::CalledStrike)
+    } else if first.starts_with("strike, swinging") || lower.contains("struck out swinging") {
+        Some(PitchResult::SwingingStrike)
+    } else if first.starts_with("foul tip") {
+        Some(PitchResult::FoulTip)
+    } else if first.starts_with("foul") {
+        Some(PitchResult::Foul)
+    } else {
+        None
+    }
+}
+
+// each check looks at one sentence at a time and is anchored on how the game words the batter's
+// part of the play, so eg. a runner walking off or an error on a throw after a hit don't count.
+// order matters here, eg. "grounds into a double play" also mentions the force out
+fn parse_outcome(sentences: &[String]) -> Option<PlayOutcome> {
+    type Matcher = fn(&str) -> bool;
+    let patterns: &[(Matcher, PlayOutcome)] = &[
+        (|s| s.contains(" triple play"), PlayOutcome::TriplePlay),
+        (|s| s.contains(" double play"), PlayOutcome::DoublePlay),
+        (
+            |s| s.contains(" homers on ") || s.contains(" hits a grand slam"),
+            PlayOutcome::HomeRun,
+        ),
+        (|s| s.contains(" sacrifice fly"), PlayOutcome::SacrificeFly),
+        (
+            |s| s.contains(" sacrifice bunt"),
+            PlayOutcome::SacrificeBunt,
+        ),
+        (
+            |s| s.contains(" fielder's choice"),
+            PlayOutcome::FieldersChoice,
+        ),
+        (
+            |s| s.contains(" force out") || s.contains(" forced out"),
+            PlayOutcome::ForceOut,
+        ),
+        (
+            |s| s.contains(" reaches on a ") && s.contains(" error"),
+            PlayOutcome::ReachedOnError,
+        ),
+        (|s| s.contains(" singles on "), PlayOutcome::Single),
+        (|s| s.contains(" doubles on "), PlayOutcome::Double),
+        (|s| s.contains(" triples on "), PlayOutcome::Triple),
+        (|s| s.contains(" grounds out"), PlayOutcome::GroundOut),
+        (|s| s.contains(" flies out"), PlayOutcome::FlyOut),
+        (|s| s.contains(" lines out"), PlayOutcome::LineOut),
+        (|s| s.contains(" pops out"), PlayOutcome::PopOut),
+        (
+            |s| s.contains(" struck out") || s.contains(" strikes out"),
+            PlayOutcome::Strikeout,
+        ),
+        (
+            |s| s.contains(" hit by the pitch") || s.contains(" hit by a pitch"),
+            PlayOutcome::HitByPitch,
+        ),
+        // "Ball 4. Name walks." and not "... walks off"
+        (
+            |s| s.ends_with(" walks") || s == "ball 4",
+            PlayOutcome::Walk,
+        ),
+        (
+            |s| s.contains(" caught stealing"),
+            PlayOutcome::CaughtStealing,
+        ),
+        (|s| s.contains(" steals "), PlayOutcome::StolenBase),
+    ];
+
+    patterns
+        .iter()
+        .find(|(matches, _)| sentences.iter().any(|s| matches(s)))
+        .map(|(_, outcome)| *outcome)
+}
+
+fn parse_batted_ball(lower: &str) -> Option<BattedBall> {
+    let patterns: &[(&[&str], BattedBall)] = &[
+        (&["bunt"], BattedBall::Bunt),
+        (
+            &["ground ball", "grounds", "grounder"],
+            BattedBall::GroundBall,
+        ),
+        (&["line drive", "lines out"], BattedBall::LineDrive),
+        (&["popup", "pop up", "pops out"], BattedBall::Popup),
+        (
+            &["fly ball", "flies out", "sacrifice fly"],
+            BattedBall::FlyBall,
+        ),
+    ];
+
+    patterns
+        .iter()
+        .find(|(needles, _)| needles.iter().any(|n| lower.contains(n)))
+        .map(|(_, kind)| *kind)
+}
+
+// every fielder mentioned, in the order they show up in the message
+fn parse_fielders(message: &str) -> Vec<&'static str> {
+    let lower = message.to_lowercase();
+    let mut found = Vec::new();
+
+    for (name, abbreviation) in FIELDER_NAMES {
+        for (pos, _) in lower.match_indices(name) {
+            found.push((pos, *abbreviation));
+        }
+    }
+
+    // double play notation, eg. "SS Name to 2B Name to 1B Name"
+    for word in message.split(|c: char| !c.is_alphanumeric()) {
+        if let Some(abbreviation) = FIELDER_ABBREVIATIONS.iter().find(|x| **x == word) {
+            let offset = word.as_ptr() as usize - message.as_ptr() as usize;
+            found.push((offset, *abbreviation));
+        }
+    }
+
+    found.sort_by_key(|(pos, _)| *pos);
+    found.into_iter().map(|(_, x)| x).collect()
+}
+
+fn parse_advance(sentence: &str) -> Option<RunnerAdvance> {
+    let base = |s: &str| match s {
+        "first base" | "first" => Some(Base::First),
+        "second base" | "second" => Some(Base::Second),
+        "third base" | "third" => Some(Base::Third),
+        "home" | "home plate" => Some(Base::Home),
+        _ => None,
+    };
+
+    if let Some(runner) = sentence.strip_suffix(" scores") {
+        return Some(RunnerAdvance {
+            runner: runner.trim().to_string(),
+            to: Base::Home,
+        });
+    }
+
+    if let Some((runner, to)) = sentence.split_once(" steals ") {
+        return Some(RunnerAdvance {
+            runner: runner.trim().to_string(),
+            to: base(to.trim())?,
+        });
+    }
+
+    // "Name to third base", but not "Name singles on a line drive to ..."
+    if sentence.contains(" on a ") || sentence.contains(" out ") {
+        return None;
+    }
+    let (runner, to) = sentence.rsplit_once(" to ")?;
+    let runner = runner.trim();
+    let runner = runner.strip_suffix(" advances").unwrap_or(runner);
+    if runner.contains(" was ") || runner.contains(" and ") {
+        return None;
+    }
+    Some(RunnerAdvance {
+        runner: runner.to_string(),
+        to: base(to.trim())?,
+    })
+}
+
+// outs the batter's part of the play accounts for, runners thrown out along the way are counted
+// separately by runner_out
+fn outcome_outs(outcome: Option<PlayOutcome>) -> i32 {
+    match outcome {
+        Some(PlayOutcome::TriplePlay) => 3,
+        Some(PlayOutcome::DoublePlay) => 2,
+        Some(
+            PlayOutcome::Strikeout
+            | PlayOutcome::GroundOut
+            | PlayOutcome::FlyOut
+            | PlayOutcome::LineOut
+            | PlayOutcome::PopOut
+            | PlayOutcome::ForceOut
+            | PlayOutcome::SacrificeFly
+            | PlayOutcome::SacrificeBunt,
+        ) => 1,
+        _ => 0,
+    }
+}
+
+// "Name out at second base.", "Name is caught stealing second base."
+fn runner_out(lower: &str) -> bool {
+    lower.contains(" out at ")
+        || lower.contains(" caught stealing")
+        || lower.contains(" doubled off")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(message: &str) -> MmolbGameEvent {
+        serde_json::from_value(serde_json::json!({
+            "event": "Test",
+            "pitcher": "Pitcher",
+            "batter": "Batter",
+            "inning": 1,
+            "inning_side": 0,
+            "message": message,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn outcomes_and_outs() {
+        let cases: &[(&str, Option<PlayOutcome>, i32)] = &[
+            ("Ball 1. 1-0.", None, 0),
+            ("Strike, looking. 0-1.", None, 0),
+            ("Foul ball. 1-1.", None, 0),
+            (
+                "Ball 4. <strong>Ann Abbott walks.</strong>",
+                Some(PlayOutcome::Walk),
+                0,
+            ),
+            (
+                "<strong>Ann Abbott struck out swinging.</strong>",
+                Some(PlayOutcome::Strikeout),
+                1,
+            ),
+            (
+                "<strong>Ann Abbott was hit by the pitch and advances to first base.</strong>",
+                Some(PlayOutcome::HitByPitch),
+                0,
+            ),
+            (
+                "<strong>Ann Abbott singles on a ground ball to SS Bo Byrd.</strong>",
+                Some(PlayOutcome::Single),
+                0,
+            ),
+            (
+                "<strong>Ann Abbott doubles on a line drive to LF Bo Byrd.</strong> Cy Cole scores!",
+                Some(PlayOutcome::Double),
+                0,
+            ),
+            (
+                "<strong>Ann Abbott triples on a fly ball to RF Bo Byrd.</strong>",
+                Some(PlayOutcome::Triple),
+                0,
+            ),
+            (
+                "<strong>Ann Abbott homers on a fly ball to center field!</strong>",
+                Some(PlayOutcome::HomeRun),
+                0,
+            ),
+            (
+                "<strong>Ann Abbott hits a grand slam on a fly ball to left field!</strong>",
+                Some(PlayOutcome::HomeRun),
+                0,
+            ),
+            (
+                "<strong>Ann Abbott grounds out to 2B Bo Byrd.</strong>",
+                Some(PlayOutcome::GroundOut),
+                1,
+            ),
+            (
+                "<strong>Ann Abbott flies out to CF Bo Byrd.</strong>",
+                Some(PlayOutcome::FlyOut),
+                1,
+            ),
+            (
+                "<strong>Ann Abbott lines out to 3B Bo Byrd.</strong>",
+                Some(PlayOutcome::LineOut),
+                1,
+            ),
+            (
+                "<strong>Ann Abbott pops out to 1B Bo Byrd.</strong>",
+                Some(PlayOutcome::PopOut),
+                1,
+            ),
+            (
+                "<strong>Ann Abbott grounds into a force out, SS Bo Byrd to 2B Di Dunn.</strong> Cy Cole out at second base.",
+                Some(PlayOutcome::ForceOut),
+                1,
+            ),
+            (
+                "<strong>Ann Abbott reaches on a fielder's choice, 3B Bo Byrd to C Di Dunn.</strong> Cy Cole out at home.",
+                Some(PlayOutcome::FieldersChoice),
+                1,
+            ),
+            (
+                "<strong>Ann Abbott grounds into a double play, SS Bo Byrd to 2B Di Dunn to 1B Ed Eads.</strong> Cy Cole out at second base.",
+                Some(PlayOutcome::DoublePlay),
+                2,
+            ),
+            (
+                "<strong>Ann Abbott grounds into a triple play, 3B Bo Byrd to 2B Di Dunn to 1B Ed Eads.</strong>",
+                Some(PlayOutcome::TriplePlay),
+                3,
+            ),
+            (
+                "<strong>Ann Abbott out on a sacrifice fly to RF Bo Byrd.</strong> Cy Cole scores!",
+                Some(PlayOutcome::SacrificeFly),
+                1,
+            ),
+            (
+                "<strong>Ann Abbott out on a sacrifice bunt, P Bo Byrd to 1B Di Dunn.</strong> Cy Cole to second base.",
+                Some(PlayOutcome::SacrificeBunt),
+                1,
+            ),
+            (
+                "<strong>Ann Abbott reaches on a throwing error by SS Bo Byrd.</strong>",
+                Some(PlayOutcome::ReachedOnError),
+                0,
+            ),
+            (
+                "<strong>Cy Cole steals second base!</strong>",
+                Some(PlayOutcome::StolenBase),
+                0,
+            ),
+            (
+                "<strong>Cy Cole is caught stealing second base.</strong>",
+                Some(PlayOutcome::CaughtStealing),
+                1,
+            ),
+            // the loose patterns used to get these wrong
+            (
+                "<strong>Ann Abbott singles on a line drive to LF Bo Byrd.</strong> Cy Cole scores! Ann Abbott walks off!",
+                Some(PlayOutcome::Single),
+                0,
+            ),
+            (
+                "<strong>Ann Abbott singles on a ground ball to CF Bo Byrd.</strong> Cy Cole to third base on a throwing error.",
+                Some(PlayOutcome::Single),
+                0,
+            ),
+            (
+                "<strong>Ann Abbott singles on a line drive to RF Bo Byrd.</strong> Cy Cole out at home.",
+                Some(PlayOutcome::Single),
+                1,
+            ),
+            (
+                "<strong>Ann Abbott struck out looking.</strong> Cy Cole is caught stealing third base.",
+                Some(PlayOutcome::Strikeout),
+                2,
+            ),
+        ];
+
+        for (message, outcome, outs) in cases {
+            let play = parse_play(&event(message), None);
+            assert_eq!(play.outcome, *outcome, "{}", message);
+            assert_eq!(play.outs_recorded, *outs, "{}", message);
+        }
+    }
+}
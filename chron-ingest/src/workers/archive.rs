@@ -0,0 +1,473 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Lines, Write},
+    path::{Path, PathBuf},
+};
+
+use chron_db::{json_hash, models::EntityKind};
+use futures::{StreamExt, TryStreamExt, stream};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use sqlx::Row;
+use time::OffsetDateTime;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use super::WorkerContext;
+
+// full dump of the raw tables (objects/versions/observations), for seeding mirrors and backups
+// a single zstd-compressed json-lines file: header line, then every object once keyed by its
+// json_hash, then the versions and observations that point at them
+const ARCHIVE_FORMAT: &str = "chron-archive";
+const ARCHIVE_VERSION: i32 = 1;
+const BATCH_SIZE: usize = 1000;
+
+#[derive(Serialize, Deserialize)]
+struct ArchiveHeader {
+    format: String,
+    version: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ArchiveRecord {
+    Object {
+        hash: Uuid,
+        data: Box<RawValue>,
+    },
+    Version {
+        kind: EntityKind,
+        entity_id: String,
+        seq: i32,
+        hash: Uuid,
+        #[serde(with = "time::serde::rfc3339")]
+        valid_from: OffsetDateTime,
+        #[serde(with = "time::serde::rfc3339::option")]
+        valid_to: Option<OffsetDateTime>,
+        #[serde(with = "time::serde::rfc3339::option")]
+        last_seen: Option<OffsetDateTime>,
+    },
+    Observation {
+        kind: EntityKind,
+        entity_id: String,
+        #[serde(with = "time::serde::rfc3339")]
+        timestamp: OffsetDateTime,
+        request_time: f64,
+        hash: Uuid,
+    },
+}
+
+pub async fn export_archive(ctx: &WorkerContext, args: &[String]) -> anyhow::Result<()> {
+    let Some(path) = args.first() else {
+        return Err(anyhow::anyhow!("usage: export-archive <path>"));
+    };
+    let path = PathBuf::from(path);
+    let tmp_path = path.with_extension("tmp");
+
+    // compressing is the slow part, so that gets its own thread
+    let (tx, mut rx) = mpsc::channel::<Vec<u8>>(64);
+    let writer_path = tmp_path.clone();
+    let writer = tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        let file = BufWriter::new(File::create(&writer_path)?);
+        let mut encoder = zstd::Encoder::new(file, 3)?;
+        while let Some(chunk) = rx.blocking_recv() {
+            encoder.write_all(&chunk)?;
+        }
+        encoder.finish()?.flush()?;
+        Ok(())
+    });
+
+    let header = ArchiveHeader {
+        format: ARCHIVE_FORMAT.to_string(),
+        version: ARCHIVE_VERSION,
+        created_at: OffsetDateTime::now_utc(),
+    };
+    let mut buf = serde_json::to_vec(&header)?;
+    buf.push(b'\n');
+
+    // objects first so importing never has a version pointing at something that isn't there yet.
+    // the table is keyed by hash, so every object only goes in once
+    let mut count = 0;
+    let mut rows = sqlx::query("select hash, data::text as data from objects").fetch(&ctx.db.pool);
+    while let Some(row) = rows.try_next().await? {
+        let data: String = row.try_get("data")?;
+        push_record(
+            &mut buf,
+            &ArchiveRecord::Object {
+                hash: row.try_get("hash")?,
+                data: RawValue::from_string(data)?,
+            },
+        )?;
+        count += 1;
+        flush(&tx, &mut buf, false).await?;
+    }
+    drop(rows);
+    info!("archived {} objects", count);
+
+    let mut count = 0;
+    let mut rows = sqlx::query(
+        "select kind, entity_id, seq, hash, valid_from, valid_to, last_seen from versions order by kind, entity_id, seq",
+    )
+    .fetch(&ctx.db.pool);
+    while let Some(row) = rows.try_next().await? {
+        push_record(
+            &mut buf,
+            &ArchiveRecord::Version {
+                kind: row.try_get("kind")?,
+                entity_id: row.try_get("entity_id")?,
+                seq: row.try_get("seq")?,
+                hash: row.try_get("hash")?,
+                valid_from: row.try_get("valid_from")?,
+                valid_to: row.try_get("valid_to")?,
+                last_seen: row.try_get("last_seen")?,
+            },
+        )?;
+        count += 1;
+        flush(&tx, &mut buf, false).await?;
+    }
+    drop(rows);
+    info!("archived {} versions", count);
+
+    let mut count = 0;
+    let mut rows = sqlx::query(
+        "select kind, entity_id, timestamp, request_time, hash from observations order by kind, entity_id, timestamp",
+    )
+    .fetch(&ctx.db.pool);
+    while let Some(row) = rows.try_next().await? {
+        push_record(
+            &mut buf,
+            &ArchiveRecord::Observation {
+                kind: row.try_get("kind")?,
+                entity_id: row.try_get("entity_id")?,
+                timestamp: row.try_get("timestamp")?,
+                request_time: row.try_get("request_time")?,
+                hash: row.try_get("hash")?,
+            },
+        )?;
+        count += 1;
+        flush(&tx, &mut buf, false).await?;
+    }
+    drop(rows);
+    info!("archived {} observations", count);
+
+    flush(&tx, &mut buf, true).await?;
+    drop(tx);
+    writer.await??;
+
+    tokio::fs::rename(&tmp_path, &path).await?;
+    info!("wrote archive to {}", path.display());
+    Ok(())
+}
+
+fn push_record(buf: &mut Vec<u8>, record: &ArchiveRecord) -> anyhow::Result<()> {
+    serde_json::to_writer(&mut *buf, record)?;
+    buf.push(b'\n');
+    Ok(())
+}
+
+async fn flush(tx: &mpsc::Sender<Vec<u8>>, buf: &mut Vec<u8>, force: bool) -> anyhow::Result<()> {
+    if force || buf.len() > 1024 * 1024 {
+        tx.send(std::mem::take(buf))
+            .await
+            .map_err(|_| anyhow::anyhow!("archive writer went away"))?;
+    }
+    Ok(())
+}
+
+// a batch of records that all have the same type, objects already hash-checked
+enum ImportBatch {
+    Objects(Vec<(Uuid, serde_json::Value)>),
+    Versions(Vec<ArchiveRecord>),
+    Observations(Vec<ArchiveRecord>),
+}
+
+// the archived versions are only taken as-is on an instance without any yet, otherwise their seqs
+// would collide or interleave with the ones already here. on top of existing data (including a
+// previous import of this archive that didn't finish) only the objects and observations go in,
+// and every entity in the archive is rebuilt from its observations, whether or not anything new
+// came in for it. so running it again after a failure leaves the same data as one clean run
+pub async fn import_archive(ctx: &WorkerContext, args: &[String]) -> anyhow::Result<()> {
+    let Some(path) = args.first() else {
+        return Err(anyhow::anyhow!("usage: import-archive <path>"));
+    };
+    let path = PathBuf::from(path);
+
+    let fresh: bool = sqlx::query_scalar("select not exists (select 1 from versions)")
+        .fetch_one(&ctx.db.pool)
+        .await?;
+    if !fresh {
+        info!("instance already has versions, will rebuild the archived entities instead");
+    }
+    let mut touched = HashSet::new();
+
+    let (tx, mut rx) = mpsc::channel::<ImportBatch>(16);
+    let reader = tokio::task::spawn_blocking(move || read_archive(path, tx));
+
+    let (mut objects, mut versions, mut observations) = (0, 0, 0);
+    let mut batches = 0;
+    while let Some(batch) = rx.recv().await {
+        match batch {
+            ImportBatch::Objects(batch) => {
+                let hashes = batch.iter().map(|(hash, _)| *hash).collect::<Vec<_>>();
+                let datas = batch.iter().map(|(_, data)| data).collect::<Vec<_>>();
+                ctx.db.save_objects_raw_bulk(&hashes, &datas).await?;
+                objects += batch.len();
+            }
+            ImportBatch::Versions(batch) => {
+                if fresh {
+                    import_versions(ctx, &batch).await?;
+                    versions += batch.len();
+                }
+            }
+            ImportBatch::Observations(batch) => {
+                observations += import_observations(ctx, &batch).await?;
+                if !fresh {
+                    touched.extend(batch.iter().filter_map(|record| match record {
+                        ArchiveRecord::Observation {
+                            kind, entity_id, ..
+                        } => Some((*kind, entity_id.clone())),
+                        _ => None,
+                    }));
+                }
+            }
+        }
+
+        batches += 1;
+        if batches % 100 == 0 {
+            info!(
+                "imported {} objects, {} versions, {} observations so far",
+                objects, versions, observations
+            );
+        }
+    }
+    reader.await??;
+
+    if !fresh {
+        info!("rebuilding {} entities", touched.len());
+        stream::iter(touched)
+            .map(|(kind, entity_id)| ctx.db.rebuild(kind, entity_id))
+            .buffer_unordered(10)
+            .try_collect::<()>()
+            .await?;
+    }
+
+    // rebuild already keeps these up to date, but anything with versions and no observations
+    // (compacted away, or never archived) still needs its row
+    info!("rebuilding latest_versions");
+    rebuild_latest_versions(ctx).await?;
+
+    info!(
+        "imported {} objects, {} versions, {} observations",
+        objects, versions, observations
+    );
+    Ok(())
+}
+
+type ArchiveLines = Lines<BufReader<zstd::Decoder<'static, BufReader<File>>>>;
+
+fn open_archive(path: &Path) -> anyhow::Result<(ArchiveHeader, ArchiveLines)> {
+    let file = BufReader::new(File::open(path)?);
+    let mut lines = BufReader::new(zstd::Decoder::with_buffer(file)?).lines();
+
+    let Some(header) = lines.next() else {
+        return Err(anyhow::anyhow!("archive is empty"));
+    };
+    let header: ArchiveHeader = serde_json::from_str(&header?)?;
+    if header.format != ARCHIVE_FORMAT || header.version != ARCHIVE_VERSION {
+        return Err(anyhow::anyhow!(
+            "unsupported archive format {} v{}",
+            header.format,
+            header.version
+        ));
+    }
+    Ok((header, lines))
+}
+
+// pulls specific objects back out of an archive (hash-checked), for `verify` repairs
+pub fn find_objects(
+    path: &Path,
+    wanted: &HashSet<Uuid>,
+) -> anyhow::Result<HashMap<Uuid, serde_json::Value>> {
+    let (_, lines) = open_archive(path)?;
+
+    let mut found = HashMap::new();
+    for line in lines {
+        // objects all come first, so we can stop at the first thing that isn't one
+        let ArchiveRecord::Object { hash, data } = serde_json::from_str::<ArchiveRecord>(&line?)?
+        else {
+            break;
+        };
+        if !wanted.contains(&hash) {
+            continue;
+        }
+
+        let (actual, data) = json_hash(serde_json::from_str(data.get())?)?;
+        if actual == hash {
+            found.insert(hash, data);
+        }
+        if found.len() == wanted.len() {
+            break;
+        }
+    }
+    Ok(found)
+}
+
+fn read_archive(path: PathBuf, tx: mpsc::Sender<ImportBatch>) -> anyhow::Result<()> {
+    let (header, lines) = open_archive(&path)?;
+    info!("importing archive from {}", header.created_at);
+
+    let send = |batch: ImportBatch| {
+        tx.blocking_send(batch)
+            .map_err(|_| anyhow::anyhow!("archive importer went away"))
+    };
+
+    let mut objects = Vec::new();
+    let mut versions = Vec::new();
+    let mut observations = Vec::new();
+    let mut mismatched = 0;
+    for line in lines {
+        match serde_json::from_str::<ArchiveRecord>(&line?)? {
+            ArchiveRecord::Object { hash, data } => {
+                // it's content-addressed, so don't take the archive's word for it
+                let (actual, data) = json_hash(serde_json::from_str(data.get())?)?;
+                if actual != hash {
+                    error!("object {} hashes to {}, skipping", hash, actual);
+                    mismatched += 1;
+                    continue;
+                }
+                objects.push((hash, data));
+                if objects.len() >= BATCH_SIZE {
+                    send(ImportBatch::Objects(std::mem::take(&mut objects)))?;
+                }
+            }
+            record @ ArchiveRecord::Version { .. } => {
+                // objects have to land before anything that references them
+                if !objects.is_empty() {
+                    send(ImportBatch::Objects(std::mem::take(&mut objects)))?;
+                }
+                versions.push(record);
+                if versions.len() >= BATCH_SIZE {
+                    send(ImportBatch::Versions(std::mem::take(&mut versions)))?;
+                }
+            }
+            record @ ArchiveRecord::Observation { .. } => {
+                if !objects.is_empty() {
+                    send(ImportBatch::Objects(std::mem::take(&mut objects)))?;
+                }
+                observations.push(record);
+                if observations.len() >= BATCH_SIZE {
+                    send(ImportBatch::Observations(std::mem::take(&mut observations)))?;
+                }
+            }
+        }
+    }
+
+    if !objects.is_empty() {
+        send(ImportBatch::Objects(objects))?;
+    }
+    if !versions.is_empty() {
+        send(ImportBatch::Versions(versions))?;
+    }
+    if !observations.is_empty() {
+        send(ImportBatch::Observations(observations))?;
+    }
+
+    if mismatched > 0 {
+        error!("skipped {} objects with mismatched hashes", mismatched);
+    }
+    Ok(())
+}
+
+async fn import_versions(ctx: &WorkerContext, batch: &[ArchiveRecord]) -> anyhow::Result<()> {
+    let (mut kinds, mut ids, mut seqs, mut hashes) = (vec![], vec![], vec![], vec![]);
+    let (mut valid_froms, mut valid_tos, mut last_seens) = (vec![], vec![], vec![]);
+    for record in batch {
+        if let ArchiveRecord::Version {
+            kind,
+            entity_id,
+            seq,
+            hash,
+            valid_from,
+            valid_to,
+            last_seen,
+        } = record
+        {
+            kinds.push(*kind);
+            ids.push(entity_id.clone());
+            seqs.push(*seq);
+            hashes.push(*hash);
+            valid_froms.push(*valid_from);
+            valid_tos.push(*valid_to);
+            last_seens.push(*last_seen);
+        }
+    }
+
+    sqlx::query("insert into versions (kind, entity_id, seq, hash, valid_from, valid_to, last_seen) select unnest($1::smallint[]), unnest($2::text[]), unnest($3::int[]), unnest($4::uuid[]), unnest($5::timestamptz[]), unnest($6::timestamptz[]), unnest($7::timestamptz[]) on conflict do nothing")
+        .bind(kinds)
+        .bind(ids)
+        .bind(seqs)
+        .bind(hashes)
+        .bind(valid_froms)
+        .bind(valid_tos)
+        .bind(last_seens)
+        .execute(&ctx.db.pool)
+        .await?;
+    Ok(())
+}
+
+// returns how many were actually new
+async fn import_observations(ctx: &WorkerContext, batch: &[ArchiveRecord]) -> anyhow::Result<u64> {
+    let (mut kinds, mut ids, mut timestamps, mut times, mut hashes) =
+        (vec![], vec![], vec![], vec![], vec![]);
+    for record in batch {
+        if let ArchiveRecord::Observation {
+            kind,
+            entity_id,
+            timestamp,
+            request_time,
+            hash,
+        } = record
+        {
+            kinds.push(*kind);
+            ids.push(entity_id.clone());
+            timestamps.push(*timestamp);
+            times.push(*request_time);
+            hashes.push(*hash);
+        }
+    }
+
+    ctx.db
+        .ensure_observation_partitions(timestamps.iter().copied())
+        .await?;
+
+    // observations don't have a key, so check for an identical one by hand
+    let res = sqlx::query("insert into observations (kind, entity_id, timestamp, request_time, hash) select * from unnest($1::smallint[], $2::text[], $3::timestamptz[], $4::float8[], $5::uuid[]) as new(kind, entity_id, timestamp, request_time, hash) where not exists (select 1 from observations o where o.kind = new.kind and o.entity_id = new.entity_id and o.timestamp = new.timestamp and o.hash = new.hash)")
+        .bind(kinds)
+        .bind(ids)
+        .bind(timestamps)
+        .bind(times)
+        .bind(hashes)
+        .execute(&ctx.db.pool)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+async fn rebuild_latest_versions(ctx: &WorkerContext) -> anyhow::Result<()> {
+    // leave "locked" rows from a rebuild_entity that's running right now alone
+    sqlx::query(
+        "insert into latest_versions (kind, entity_id, seq, hash, valid_from)
+            select distinct on (kind, entity_id) kind, entity_id, seq, hash, valid_from
+                from versions
+                order by kind, entity_id, seq desc
+            on conflict (kind, entity_id) do update
+                set seq = excluded.seq, hash = excluded.hash, valid_from = excluded.valid_from
+                where latest_versions.valid_from < '9999-01-01T00:00:00Z'",
+    )
+    .execute(&ctx.db.pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use std::time::Duration;
+
+use chron_db::models::EntityKind;
+use futures::{StreamExt, stream};
+use strum::VariantArray;
+use time::OffsetDateTime;
+use tracing::{error, info};
+
+use super::{IntervalWorker, WorkerContext};
+
+// an observation with the same hash on both sides of it doesn't tell us anything, so those get
+// deleted. the first and last of every run stay, which is exactly what rebuild_entity and
+// add_version look at for valid_from/valid_to/last_seen, so versions come out the same.
+// ctid is only unique within one partition, so rows are picked out by (tableoid, ctid).
+// late observations can't be fitted into a run once its middle is gone, so the cutoff goes into
+// compaction_progress first and add_version refuses anything older than keep_days from then on
+const COMPACT_ENTITY_QUERY: &str = "
+    with runs as (
+        select tableoid, ctid, timestamp,
+            hash = lag(hash) over w and hash = lead(hash) over w as redundant
+        from observations
+        where kind = $1 and entity_id = $2
+        window w as (order by timestamp, tableoid, ctid)
+    )";
+
+pub struct CompactObservations;
+
+impl IntervalWorker for CompactObservations {
+    fn interval() -> tokio::time::Interval {
+        tokio::time::interval(Duration::from_secs(60 * 60 * 24))
+    }
+
+    async fn tick(&mut self, ctx: &mut WorkerContext) -> anyhow::Result<()> {
+        compact_observations(ctx, false).await
+    }
+}
+
+pub async fn compact_observations(ctx: &WorkerContext, dry_run: bool) -> anyhow::Result<()> {
+    let mut total = 0;
+    for kind in EntityKind::VARIANTS {
+        let Some(keep_days) = keep_days(ctx, *kind)? else {
+            continue;
+        };
+        let cutoff = OffsetDateTime::now_utc() - time::Duration::days(keep_days);
+        if !dry_run {
+            // before deleting anything, so late observations for that span get turned away from here on
+            sqlx::query("insert into compaction_progress (kind, compacted_before) values ($1, $2) on conflict (kind) do update set compacted_before = greatest(compaction_progress.compacted_before, excluded.compacted_before)")
+                .bind(kind)
+                .bind(cutoff)
+                .execute(&ctx.db.pool)
+                .await?;
+        }
+
+        let reclaimed = compact_kind(ctx, *kind, cutoff, dry_run).await?;
+        if reclaimed > 0 {
+            info!(
+                "{} {} {:?} observations older than {} days",
+                if dry_run { "would remove" } else { "removed" },
+                reclaimed,
+                kind,
+                keep_days
+            );
+        }
+        total += reclaimed;
+    }
+
+    info!(
+        "{} {} observations in total",
+        if dry_run { "would remove" } else { "removed" },
+        total
+    );
+    Ok(())
+}
+
+// None if this kind shouldn't be compacted at all
+fn keep_days(ctx: &WorkerContext, kind: EntityKind) -> anyhow::Result<Option<i64>> {
+    let config = &ctx.config.compaction;
+    let name = serde_json::to_value(kind)?;
+    let name = name.as_str().unwrap_or_default();
+
+    if config.skip.iter().any(|x| x == name) {
+        return Ok(None);
+    }
+    Ok(Some(
+        config.kinds.get(name).copied().unwrap_or(config.keep_days),
+    ))
+}
+
+async fn compact_kind(
+    ctx: &WorkerContext,
+    kind: EntityKind,
+    cutoff: OffsetDateTime,
+    dry_run: bool,
+) -> anyhow::Result<u64> {
+    let ids: Vec<String> =
+        sqlx::query_scalar("select distinct entity_id from observations where kind = $1")
+            .bind(kind)
+            .fetch_all(&ctx.db.pool)
+            .await?;
+
+    // one entity at a time so we're never holding locks on a whole kind's worth of rows
+    let reclaimed = stream::iter(ids)
+        .map(|id| compact_entity(ctx, kind, id, cutoff, dry_run))
+        .buffer_unordered(10)
+        .fold(0, |acc, x| async move {
+            match x {
+                Ok(n) => acc + n,
+                Err(e) => {
+                    error!("error compacting {:?} observations: {:?}", kind, e);
+                    acc
+                }
+            }
+        })
+        .await;
+    Ok(reclaimed)
+}
+
+async fn compact_entity(
+    ctx: &WorkerContext,
+    kind: EntityKind,
+    entity_id: String,
+    cutoff: OffsetDateTime,
+    dry_run: bool,
+) -> anyhow::Result<u64> {
+    if dry_run {
+        let count: i64 = sqlx::query_scalar(&format!(
+            "{} select count(*) from runs where redundant and timestamp < $3",
+            COMPACT_ENTITY_QUERY
+        ))
+        .bind(kind)
+        .bind(entity_id)
+        .bind(cutoff)
+        .fetch_one(&ctx.db.pool)
+        .await?;
+        return Ok(count as u64);
+    }
+
+    let res = sqlx::query(&format!(
+        "{} delete from observations o using runs where o.kind = $1 and o.entity_id = $2 and o.tableoid = runs.tableoid and o.ctid = runs.ctid and runs.redundant and runs.timestamp < $3",
+        COMPACT_ENTITY_QUERY
+    ))
+    .bind(kind)
+    .bind(entity_id)
+    .bind(cutoff)
+    .execute(&ctx.db.pool)
+    .await?;
+    Ok(res.rows_affected())
+}
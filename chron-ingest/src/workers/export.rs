@@ -0,0 +1,487 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use arrow::{
+    array::{
+        ArrayRef, BooleanBuilder, Float32Builder, Float64Builder, Int16Builder, Int32Builder,
+        Int64Builder, RecordBatch, StringBuilder, TimestampMicrosecondBuilder,
+    },
+    datatypes::{DataType, Field, Schema, TimeUnit},
+};
+use chron_db::models::EntityKind;
+use futures::TryStreamExt;
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Column, Row, TypeInfo, postgres::PgRow};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tokio::{sync::mpsc, task::JoinHandle};
+use tracing::{error, info};
+
+use super::{IntervalWorker, WorkerContext};
+
+const BATCH_SIZE: usize = 8192;
+
+pub struct ExportParquet;
+
+impl IntervalWorker for ExportParquet {
+    fn interval() -> tokio::time::Interval {
+        tokio::time::interval(Duration::from_secs(60 * 60 * 6))
+    }
+
+    async fn tick(&mut self, ctx: &mut WorkerContext) -> anyhow::Result<()> {
+        export_all(ctx).await
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Partition {
+    None,
+    Season,
+    Kind,
+}
+
+struct ExportTable {
+    name: &'static str,
+    partition: Partition,
+    // takes the season or kind as $1 if partitioned.
+    // json/uuid columns need casting to text, see `ColumnBuilder`
+    query: &'static str,
+    // same filter as `query`, used to skip partitions that haven't changed. rows get updated in
+    // place (stats, event observed_at, version last_seen) so a row count isn't enough, this sums a
+    // hash of every row instead. still a lot cheaper than writing the parquet file
+    signature_query: Option<&'static str>,
+}
+
+const TABLES: &[ExportTable] = &[
+    ExportTable {
+        name: "games",
+        partition: Partition::Season,
+        query: "select game_id, season, day, day_special, home_team_id, away_team_id, state, event_count, last_update::text as last_update from games where season = $1 order by game_id",
+        signature_query: Some(
+            "select count(*) || '-' || coalesce(sum(hashtextextended(x::text, 0)::numeric), 0) from games x where season = $1",
+        ),
+    },
+    ExportTable {
+        name: "game_events",
+        partition: Partition::Season,
+        query: "select game_id, index, season, day, observed_at, pitcher_id, batter_id, data::text as data from game_events where season = $1 order by game_id, index",
+        signature_query: Some(
+            "select count(*) || '-' || coalesce(sum(hashtextextended(x::text, 0)::numeric), 0) from game_events x where season = $1",
+        ),
+    },
+    // the exploded one, proper columns are a lot nicer to work with than a json blob
+    ExportTable {
+        name: "game_player_stats",
+        partition: Partition::Season,
+        query: "select * from game_player_stats_exploded where season = $1 order by game_id, team_id, player_id",
+        signature_query: Some(
+            "select count(*) || '-' || coalesce(sum(hashtextextended(x::text, 0)::numeric), 0) from game_player_stats_exploded x where season = $1",
+        ),
+    },
+    ExportTable {
+        name: "teams",
+        partition: Partition::None,
+        query: "select * from teams order by team_id",
+        signature_query: None,
+    },
+    ExportTable {
+        name: "leagues",
+        partition: Partition::None,
+        query: "select * from leagues order by league_id",
+        signature_query: None,
+    },
+    ExportTable {
+        name: "players",
+        partition: Partition::None,
+        query: "select * from players order by player_id",
+        signature_query: None,
+    },
+    ExportTable {
+        name: "versions",
+        partition: Partition::Kind,
+        query: "select v.kind, v.entity_id, v.seq, v.valid_from, v.valid_to, v.last_seen, v.hash::text as hash, o.data::text as data from versions v inner join objects o using (hash) where v.kind = $1 order by v.entity_id, v.seq",
+        signature_query: Some(
+            "select count(*) || '-' || coalesce(sum(hashtextextended(x::text, 0)::numeric), 0) from versions x where kind = $1",
+        ),
+    },
+];
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct ExportManifest {
+    generated_at: String,
+    tables: BTreeMap<String, Vec<ExportFile>>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExportFile {
+    // relative to the export dir, hive-style so duckdb/polars pick up the partition columns
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    season: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<EntityKind>,
+    rows: i64,
+    bytes: u64,
+    written_at: String,
+    // from `signature_query` when it was written
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+}
+
+pub async fn export_all(ctx: &WorkerContext) -> anyhow::Result<()> {
+    let Some(export_path) = &ctx.config.export_path else {
+        info!("no export_path configured, not exporting");
+        return Ok(());
+    };
+    let root = PathBuf::from(export_path);
+    tokio::fs::create_dir_all(&root).await?;
+
+    let manifest_path = root.join("manifest.json");
+    let previous: ExportManifest = match tokio::fs::read(&manifest_path).await {
+        Ok(data) => serde_json::from_slice(&data).unwrap_or_default(),
+        Err(_) => ExportManifest::default(),
+    };
+
+    let seasons: Vec<i32> = sqlx::query_scalar("select distinct season from games order by 1")
+        .fetch_all(&ctx.db.pool)
+        .await?;
+    let kinds: Vec<i16> = sqlx::query_scalar("select distinct kind from versions order by 1")
+        .fetch_all(&ctx.db.pool)
+        .await?;
+
+    let mut manifest = ExportManifest {
+        generated_at: now_string()?,
+        tables: BTreeMap::new(),
+    };
+    for table in TABLES {
+        let previous_files = previous.tables.get(table.name);
+        let files = match export_table(ctx, &root, table, &seasons, &kinds, previous_files).await {
+            Ok(files) => files,
+            Err(e) => {
+                // eg. the players matview not existing yet, don't let it take the rest down with it
+                error!("failed to export {}: {:?}", table.name, e);
+                previous_files.cloned().unwrap_or_default()
+            }
+        };
+        manifest.tables.insert(table.name.to_string(), files);
+    }
+
+    let tmp_path = root.join("manifest.json.tmp");
+    tokio::fs::write(&tmp_path, serde_json::to_vec_pretty(&manifest)?).await?;
+    tokio::fs::rename(&tmp_path, &manifest_path).await?;
+    info!("export done");
+    Ok(())
+}
+
+async fn export_table(
+    ctx: &WorkerContext,
+    root: &Path,
+    table: &ExportTable,
+    seasons: &[i32],
+    kinds: &[i16],
+    previous: Option<&Vec<ExportFile>>,
+) -> anyhow::Result<Vec<ExportFile>> {
+    let mut files = Vec::new();
+    match table.partition {
+        Partition::None => {
+            let path = format!("{}/{}.parquet", table.name, table.name);
+            let query = sqlx::query(table.query);
+            if let Some(file) = export_query(ctx, root, &path, query).await? {
+                files.push(file);
+            }
+        }
+        Partition::Season => {
+            for season in seasons {
+                let path = format!("{}/season={}/{}.parquet", table.name, season, table.name);
+                let signature = partition_signature(ctx, table, *season as i64).await?;
+                let file = match find_unchanged(root, previous, &path, &signature) {
+                    Some(file) => Some(file),
+                    None => {
+                        info!("exporting {} for season {}", table.name, season);
+                        let query = sqlx::query(table.query).bind(season);
+                        export_query(ctx, root, &path, query)
+                            .await?
+                            .map(|file| ExportFile { signature, ..file })
+                    }
+                };
+                if let Some(mut file) = file {
+                    file.season = Some(*season);
+                    files.push(file);
+                }
+            }
+        }
+        Partition::Kind => {
+            for kind in kinds {
+                let Some(entity_kind) = EntityKind::from_repr(*kind) else {
+                    continue;
+                };
+                let kind_name = serde_json::to_value(entity_kind)?;
+                let kind_name = kind_name.as_str().unwrap_or_default();
+                let path = format!("{}/kind={}/{}.parquet", table.name, kind_name, table.name);
+                let signature = partition_signature(ctx, table, *kind as i64).await?;
+                let file = match find_unchanged(root, previous, &path, &signature) {
+                    Some(file) => Some(file),
+                    None => {
+                        info!("exporting {} for {:?}", table.name, entity_kind);
+                        let query = sqlx::query(table.query).bind(kind);
+                        export_query(ctx, root, &path, query)
+                            .await?
+                            .map(|file| ExportFile { signature, ..file })
+                    }
+                };
+                if let Some(mut file) = file {
+                    file.kind = Some(entity_kind);
+                    files.push(file);
+                }
+            }
+        }
+    }
+    Ok(files)
+}
+
+async fn partition_signature(
+    ctx: &WorkerContext,
+    table: &ExportTable,
+    partition_value: i64,
+) -> anyhow::Result<Option<String>> {
+    let Some(signature_query) = table.signature_query else {
+        return Ok(None);
+    };
+
+    let signature: String = match table.partition {
+        Partition::Kind => {
+            sqlx::query_scalar(signature_query)
+                .bind(partition_value as i16)
+                .fetch_one(&ctx.db.pool)
+                .await?
+        }
+        _ => {
+            sqlx::query_scalar(signature_query)
+                .bind(partition_value as i32)
+                .fetch_one(&ctx.db.pool)
+                .await?
+        }
+    };
+    Ok(Some(signature))
+}
+
+// old seasons hardly ever change, so skip them if nothing in them has since last time
+fn find_unchanged(
+    root: &Path,
+    previous: Option<&Vec<ExportFile>>,
+    path: &str,
+    signature: &Option<String>,
+) -> Option<ExportFile> {
+    let prev = previous?.iter().find(|f| f.path == path)?;
+    if signature.is_none() || prev.signature != *signature || !root.join(path).exists() {
+        return None;
+    }
+    Some(prev.clone())
+}
+
+// writes to a temp file first so nobody downloads half a parquet file.
+// returns None if there were no rows at all
+async fn export_query<'q>(
+    ctx: &WorkerContext,
+    root: &Path,
+    path: &str,
+    query: sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments>,
+) -> anyhow::Result<Option<ExportFile>> {
+    let final_path = root.join(path);
+    if let Some(parent) = final_path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let tmp_path = final_path.with_extension("parquet.tmp");
+
+    // the parquet writing is blocking file io (and zstd), so it gets its own thread and batches are
+    // handed over as they fill up
+    let mut writer: Option<WriterTask> = None;
+    let mut builders: Vec<ColumnBuilder> = Vec::new();
+    let mut pending = 0;
+    let mut rows = 0i64;
+
+    let mut stream = query.fetch(&ctx.db.pool);
+    while let Some(row) = stream.try_next().await? {
+        if writer.is_none() {
+            let (schema, new_builders) = schema_for_row(&row)?;
+            let schema = Arc::new(schema);
+            let (tx, rx) = mpsc::channel(2);
+            let handle = tokio::task::spawn_blocking({
+                let tmp_path = tmp_path.clone();
+                let schema = schema.clone();
+                move || write_parquet(&tmp_path, schema, rx)
+            });
+            writer = Some(WriterTask {
+                batches: tx,
+                handle,
+                schema,
+            });
+            builders = new_builders;
+        }
+
+        for (i, builder) in builders.iter_mut().enumerate() {
+            builder.append(&row, i)?;
+        }
+        pending += 1;
+        rows += 1;
+
+        if pending >= BATCH_SIZE {
+            if let Some(writer) = writer.as_ref() {
+                // only fails if the writer gave up, its error comes out of the handle below
+                let batch = finish_batch(&writer.schema, &mut builders)?;
+                if writer.batches.send(batch).await.is_err() {
+                    break;
+                }
+            }
+            pending = 0;
+        }
+    }
+
+    let Some(writer) = writer else {
+        return Ok(None);
+    };
+    if pending > 0 {
+        // same as above
+        let batch = finish_batch(&writer.schema, &mut builders)?;
+        let _ = writer.batches.send(batch).await;
+    }
+    drop(writer.batches);
+    writer.handle.await??;
+
+    tokio::fs::rename(&tmp_path, &final_path).await?;
+    let bytes = tokio::fs::metadata(&final_path).await?.len();
+    info!("wrote {} ({} rows, {} bytes)", path, rows, bytes);
+
+    Ok(Some(ExportFile {
+        path: path.to_string(),
+        season: None,
+        kind: None,
+        rows,
+        bytes,
+        written_at: now_string()?,
+        signature: None,
+    }))
+}
+
+struct WriterTask {
+    batches: mpsc::Sender<RecordBatch>,
+    handle: JoinHandle<anyhow::Result<()>>,
+    schema: Arc<Schema>,
+}
+
+fn finish_batch(
+    schema: &Arc<Schema>,
+    builders: &mut [ColumnBuilder],
+) -> anyhow::Result<RecordBatch> {
+    let columns: Vec<ArrayRef> = builders.iter_mut().map(|b| b.finish()).collect();
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+fn write_parquet(
+    path: &Path,
+    schema: Arc<Schema>,
+    mut batches: mpsc::Receiver<RecordBatch>,
+) -> anyhow::Result<()> {
+    let props = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .build();
+    let file = std::fs::File::create(path)?;
+    let mut writer = ArrowWriter::try_new(file, schema, Some(props))?;
+    while let Some(batch) = batches.blocking_recv() {
+        writer.write(&batch)?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+fn now_string() -> anyhow::Result<String> {
+    Ok(OffsetDateTime::now_utc().format(&Rfc3339)?)
+}
+
+enum ColumnBuilder {
+    Bool(BooleanBuilder),
+    Int16(Int16Builder),
+    Int32(Int32Builder),
+    Int64(Int64Builder),
+    Float32(Float32Builder),
+    Float64(Float64Builder),
+    Utf8(StringBuilder),
+    Timestamp(TimestampMicrosecondBuilder),
+}
+
+fn schema_for_row(row: &PgRow) -> anyhow::Result<(Schema, Vec<ColumnBuilder>)> {
+    let mut fields = Vec::new();
+    let mut builders = Vec::new();
+    for col in row.columns() {
+        let (data_type, builder) = match col.type_info().name() {
+            "BOOL" => (
+                DataType::Boolean,
+                ColumnBuilder::Bool(BooleanBuilder::new()),
+            ),
+            "INT2" => (DataType::Int16, ColumnBuilder::Int16(Int16Builder::new())),
+            "INT4" => (DataType::Int32, ColumnBuilder::Int32(Int32Builder::new())),
+            "INT8" => (DataType::Int64, ColumnBuilder::Int64(Int64Builder::new())),
+            "FLOAT4" => (
+                DataType::Float32,
+                ColumnBuilder::Float32(Float32Builder::new()),
+            ),
+            "FLOAT8" => (
+                DataType::Float64,
+                ColumnBuilder::Float64(Float64Builder::new()),
+            ),
+            "TEXT" | "VARCHAR" => (DataType::Utf8, ColumnBuilder::Utf8(StringBuilder::new())),
+            "TIMESTAMPTZ" => (
+                DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+                ColumnBuilder::Timestamp(TimestampMicrosecondBuilder::new().with_timezone("UTC")),
+            ),
+            other => anyhow::bail!(
+                "can't export column {} of type {}, cast it to text",
+                col.name(),
+                other
+            ),
+        };
+        fields.push(Field::new(col.name(), data_type, true));
+        builders.push(builder);
+    }
+    Ok((Schema::new(fields), builders))
+}
+
+impl ColumnBuilder {
+    fn append(&mut self, row: &PgRow, i: usize) -> anyhow::Result<()> {
+        match self {
+            ColumnBuilder::Bool(b) => b.append_option(row.try_get::<Option<bool>, _>(i)?),
+            ColumnBuilder::Int16(b) => b.append_option(row.try_get::<Option<i16>, _>(i)?),
+            ColumnBuilder::Int32(b) => b.append_option(row.try_get::<Option<i32>, _>(i)?),
+            ColumnBuilder::Int64(b) => b.append_option(row.try_get::<Option<i64>, _>(i)?),
+            ColumnBuilder::Float32(b) => b.append_option(row.try_get::<Option<f32>, _>(i)?),
+            ColumnBuilder::Float64(b) => b.append_option(row.try_get::<Option<f64>, _>(i)?),
+            ColumnBuilder::Utf8(b) => b.append_option(row.try_get::<Option<&str>, _>(i)?),
+            ColumnBuilder::Timestamp(b) => b.append_option(
+                row.try_get::<Option<OffsetDateTime>, _>(i)?
+                    .map(|x| (x.unix_timestamp_nanos() / 1000) as i64),
+            ),
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            ColumnBuilder::Bool(b) => Arc::new(b.finish()),
+            ColumnBuilder::Int16(b) => Arc::new(b.finish()),
+            ColumnBuilder::Int32(b) => Arc::new(b.finish()),
+            ColumnBuilder::Int64(b) => Arc::new(b.finish()),
+            ColumnBuilder::Float32(b) => Arc::new(b.finish()),
+            ColumnBuilder::Float64(b) => Arc::new(b.finish()),
+            ColumnBuilder::Utf8(b) => Arc::new(b.finish()),
+            ColumnBuilder::Timestamp(b) => Arc::new(b.finish()),
+        }
+    }
+}
@@ -19,6 +19,7 @@ use tracing::{error, info, warn};
 
 use crate::{
     models::{GameDayNumber, MmolbDay, MmolbGame, MmolbGameEvent, MmolbSeason, MmolbTeam},
+    plays,
     workers::{IntervalWorker, WorkerContext, league},
 };
 use futures::{StreamExt, TryStreamExt};
@@ -361,10 +362,21 @@ async fn save_game_events(
     let away_team = try_get_team(&ctx.db, &game.away_team_id, &timestamp).await?;
     let home_team = try_get_team(&ctx.db, &game.home_team_id, &timestamp).await?;
 
+    // the play parser wants the event before this batch for score diffs
+    let mut prev_event = if start_idx > 0 {
+        ctx.db
+            .get_game_event(game.game_id, start_idx - 1)
+            .await?
+            .and_then(|x| MmolbGameEvent::deserialize(&x.data).ok())
+    } else {
+        None
+    };
+
     let mut indexes = Vec::new();
     let mut datas = Vec::new();
     let mut pitchers = Vec::new();
     let mut batters = Vec::new();
+    let mut plays = Vec::new();
     for (idx, evt) in raw_events.iter().enumerate() {
         let absolute_idx = idx as i32 + start_idx;
 
@@ -382,12 +394,28 @@ async fn save_game_events(
                 let batter_id = batting_team
                     .zip(parsed_event.batter.as_ref())
                     .and_then(|(t, name)| try_find_player_by_name(t, name, "Batter"));
+
+                let play = plays::parse_play(&parsed_event, prev_event.as_ref());
+                let mut play = plays::to_db_play(
+                    game.game_id,
+                    absolute_idx,
+                    game.season,
+                    game.day.to_int(),
+                    &parsed_event,
+                    play,
+                );
+                play.pitcher_id = pitcher_id.clone();
+                play.batter_id = batter_id.clone();
+                plays.push(play);
+                prev_event = Some(parsed_event);
+
                 Some(EnrichedGameEvent {
                     pitcher_id,
                     batter_id,
                 })
             }
             Err(e) => {
+                prev_event = None;
                 let s = serde_json::to_string(evt);
                 warn!(
                     "couldn't parse game event {}/{} ({:?}): {:?}",
@@ -415,6 +443,47 @@ async fn save_game_events(
             &batters,
         )
         .await?;
+    ctx.db.update_plays(&plays).await?;
+    Ok(())
+}
+
+pub async fn rebuild_plays(ctx: &WorkerContext) -> anyhow::Result<()> {
+    let game_ids = ctx.db.get_all_game_ids().await?;
+    ctx.process_many_with_progress(game_ids, 20, "rebuild plays", |ctx, g| {
+        rebuild_game_plays(ctx, g)
+    })
+    .await;
+    Ok(())
+}
+
+// reparses already-saved events, no fetching or player lookups needed
+async fn rebuild_game_plays(ctx: &WorkerContext, game_id: String) -> anyhow::Result<()> {
+    let events = ctx.db.get_game_events(&game_id, 0).await?;
+
+    let mut prev_event = None;
+    let mut plays = Vec::with_capacity(events.len());
+    for evt in events {
+        let Ok(parsed_event) = MmolbGameEvent::deserialize(&evt.data) else {
+            prev_event = None;
+            continue;
+        };
+
+        let play = plays::parse_play(&parsed_event, prev_event.as_ref());
+        let mut play = plays::to_db_play(
+            &game_id,
+            evt.index,
+            evt.season as i32,
+            evt.day as i32,
+            &parsed_event,
+            play,
+        );
+        play.pitcher_id = evt.pitcher_id;
+        play.batter_id = evt.batter_id;
+        plays.push(play);
+        prev_event = Some(parsed_event);
+    }
+
+    ctx.db.update_plays(&plays).await?;
     Ok(())
 }
 
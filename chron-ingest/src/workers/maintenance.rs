@@ -2,6 +2,7 @@ use std::time::Duration;
 
 use chron_db::models::EntityKind;
 use strum::VariantArray;
+use time::OffsetDateTime;
 use tracing::info;
 
 use crate::workers::IntervalWorker;
@@ -31,6 +32,36 @@ pub async fn recompress(ctx: &WorkerContext) -> anyhow::Result<()> {
     Ok(())
 }
 
+pub struct CreatePartitions;
+
+impl IntervalWorker for CreatePartitions {
+    fn interval() -> tokio::time::Interval {
+        tokio::time::interval(Duration::from_secs(60 * 60 * 6))
+    }
+
+    async fn tick(&mut self, ctx: &mut WorkerContext) -> anyhow::Result<()> {
+        create_partitions(ctx).await
+    }
+}
+
+// inserts create their own partitions if they have to, but doing it ahead of time keeps the ddl
+// off the hot path when the month/season rolls over
+pub async fn create_partitions(ctx: &WorkerContext) -> anyhow::Result<()> {
+    let now = OffsetDateTime::now_utc();
+    ctx.db
+        .ensure_observation_partitions((0..3).map(|i| now + time::Duration::days(31 * i)))
+        .await?;
+
+    let season: Option<i32> = sqlx::query_scalar("select max(season) from games")
+        .fetch_one(&ctx.db.pool)
+        .await?;
+    if let Some(season) = season {
+        ctx.db.ensure_game_events_partition(season).await?;
+        ctx.db.ensure_game_events_partition(season + 1).await?;
+    }
+    Ok(())
+}
+
 pub struct FixupGameStatsNames;
 
 impl IntervalWorker for FixupGameStatsNames {
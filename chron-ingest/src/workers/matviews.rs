@@ -12,7 +12,18 @@ impl IntervalWorker for RefreshMatviews {
     }
 
     async fn tick(&mut self, ctx: &mut super::WorkerContext) -> anyhow::Result<()> {
-        let matviews = ["players", "team_feeds", "rosters", "roster_slot_history"];
+        // order matters for the last few, each one is built from the ones before it
+        let matviews = [
+            "players",
+            "team_feeds",
+            "rosters",
+            "roster_slot_history",
+            "base_out_states",
+            "re24_matrix",
+            "win_expectancy",
+            "win_probability_events",
+            "pitches",
+        ];
         for matview in matviews {
             info!("refreshing matview {}...", matview);
 
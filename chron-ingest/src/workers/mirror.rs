@@ -0,0 +1,280 @@
+use std::{collections::HashSet, time::Duration};
+
+use chron_db::{
+    ChronDb, json_hash,
+    models::{EntityKind, IsoDateTime, PageToken},
+    storage::Storage,
+};
+use reqwest::Url;
+use serde::Deserialize;
+use strum::VariantArray;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use super::{IntervalWorker, WorkerContext};
+
+// backfills history from another chron instance's /chron/v0/versions instead of re-polling mmolb
+// progress is saved per kind after every page, so it picks up where it left off
+const PAGE_SIZE: usize = 1000;
+// the source can still change behind the saved cursor: late observations get fitted into older
+// versions, and a version only gets its last_seen once the next one shows up. so every pass starts
+// this far back and reads it again, save_page skips whatever we already have. anything older than
+// this needs the mirror_progress row deleted to pick it up
+const RESCAN_WINDOW: time::Duration = time::Duration::days(1);
+
+pub struct FollowMirror;
+
+impl IntervalWorker for FollowMirror {
+    fn interval() -> tokio::time::Interval {
+        tokio::time::interval(Duration::from_secs(60))
+    }
+
+    async fn tick(&mut self, ctx: &mut WorkerContext) -> anyhow::Result<()> {
+        mirror_all(ctx).await
+    }
+}
+
+// same as the worker, for running a mirror without polling mmolb at all
+pub async fn follow_mirror(ctx: &WorkerContext) -> anyhow::Result<()> {
+    let mut interval = FollowMirror::interval();
+    loop {
+        interval.tick().await;
+        if let Err(e) = mirror_all(ctx).await {
+            error!("error mirroring: {:?}", e);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct MirrorPage {
+    items: Vec<MirrorVersion>,
+    next_page: Option<String>,
+}
+
+// valid_to isn't needed, the next version's valid_from closes this one off on our side too
+#[derive(Deserialize)]
+struct MirrorVersion {
+    entity_id: String,
+    valid_from: IsoDateTime,
+    // missing for the latest version, and from instances that don't send it yet
+    #[serde(default)]
+    last_seen: Option<IsoDateTime>,
+    data: serde_json::Value,
+}
+
+pub async fn mirror_all(ctx: &WorkerContext) -> anyhow::Result<()> {
+    let Some(source) = &ctx.config.mirror_url else {
+        info!("no mirror_url configured, not mirroring");
+        return Ok(());
+    };
+    let source = source.trim_end_matches('/');
+
+    for kind in EntityKind::VARIANTS {
+        // one kind failing (eg. the other side being on an older version) shouldn't stop the rest
+        if let Err(e) = mirror_kind(ctx, source, *kind).await {
+            error!("error mirroring {:?}: {:?}", kind, e);
+        }
+    }
+    Ok(())
+}
+
+async fn mirror_kind(ctx: &WorkerContext, source: &str, kind: EntityKind) -> anyhow::Result<()> {
+    let kind_name = serde_json::to_value(kind)?;
+    let kind_name = kind_name.as_str().unwrap_or_default();
+
+    let saved: Option<String> =
+        sqlx::query_scalar("select page from mirror_progress where source = $1 and kind = $2")
+            .bind(source)
+            .bind(kind)
+            .fetch_optional(&ctx.db.pool)
+            .await?;
+    let after = match saved {
+        Some(page) => {
+            let token = page.parse::<PageToken>()?;
+            Some((token.timestamp - RESCAN_WINDOW).format(&Rfc3339)?)
+        }
+        None => None,
+    };
+    let mut page: Option<String> = None;
+
+    let mut count = 0;
+    let mut added = 0;
+    loop {
+        let mut url = Url::parse(&format!("{}/chron/v0/versions", source))?;
+        url.query_pairs_mut()
+            .append_pair("kind", kind_name)
+            .append_pair("count", &PAGE_SIZE.to_string());
+        if let Some(after) = &after {
+            url.query_pairs_mut().append_pair("after", after);
+        }
+        if let Some(page) = &page {
+            url.query_pairs_mut().append_pair("page", page);
+        }
+
+        let res: MirrorPage = ctx.client.fetch(url).await?.parse()?;
+        if res.items.is_empty() {
+            break;
+        }
+
+        let items = res.items.len();
+        added += save_page(&ctx.db, kind, res.items).await?;
+        count += items;
+
+        let Some(next_page) = res.next_page else {
+            break;
+        };
+        sqlx::query("insert into mirror_progress (source, kind, page, updated_at) values ($1, $2, $3, now()) on conflict (source, kind) do update set page = excluded.page, updated_at = excluded.updated_at")
+            .bind(source)
+            .bind(kind)
+            .bind(&next_page)
+            .execute(&ctx.db.pool)
+            .await?;
+        page = Some(next_page);
+
+        if items < PAGE_SIZE {
+            break;
+        }
+        info!("mirrored {} {:?} versions so far", count, kind);
+    }
+
+    if added > 0 {
+        info!("mirrored {} {:?} observations from {}", added, kind, source);
+    }
+    Ok(())
+}
+
+// each version becomes an observation when it started and one when it was last seen, so it
+// covers the same span here as it does on the source. these come back in valid_from order, which
+// keeps add_version off its slower late path. returns how many observations were new
+async fn save_page(
+    db: &ChronDb,
+    kind: EntityKind,
+    versions: Vec<MirrorVersion>,
+) -> anyhow::Result<usize> {
+    let hashed = tokio::task::spawn_blocking(move || {
+        versions
+            .into_iter()
+            .map(|version| {
+                let (hash, data) = json_hash(version.data)?;
+                Ok((
+                    version.entity_id,
+                    version.valid_from.0,
+                    version.last_seen.map(|x| x.0),
+                    hash,
+                    data,
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+    })
+    .await??;
+
+    let hashes = hashed.iter().map(|x| x.3).collect::<Vec<_>>();
+    let datas = hashed.iter().map(|x| &x.4).collect::<Vec<_>>();
+    db.save_objects_raw_bulk(&hashes, &datas).await?;
+
+    let mut observations = Vec::new();
+    for (entity_id, valid_from, last_seen, hash, _) in &hashed {
+        observations.push((entity_id.clone(), *valid_from, *hash));
+        if let Some(last_seen) = last_seen.filter(|x| x > valid_from) {
+            observations.push((entity_id.clone(), last_seen, *hash));
+        }
+    }
+
+    // a page gets fetched again if we stopped before saving its progress, same check as
+    // import_observations so that doesn't add everything a second time
+    let existing: HashSet<(String, OffsetDateTime, Uuid)> = sqlx::query_as("select o.entity_id, o.timestamp, o.hash from observations o inner join unnest($2::text[], $3::timestamptz[], $4::uuid[]) as new(entity_id, timestamp, hash) on o.entity_id = new.entity_id and o.timestamp = new.timestamp and o.hash = new.hash where o.kind = $1")
+        .bind(kind)
+        .bind(observations.iter().map(|x| x.0.clone()).collect::<Vec<_>>())
+        .bind(observations.iter().map(|x| x.1).collect::<Vec<_>>())
+        .bind(observations.iter().map(|x| x.2).collect::<Vec<_>>())
+        .fetch_all(&db.pool)
+        .await?
+        .into_iter()
+        .collect();
+
+    let mut added = 0;
+    for observation in observations {
+        if existing.contains(&observation) {
+            continue;
+        }
+        let (entity_id, timestamp, hash) = observation;
+        Storage::add_version(db, kind, &entity_id, hash, timestamp, time::Duration::ZERO).await?;
+        added += 1;
+    }
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::postgres::PgPoolOptions;
+
+    use super::*;
+
+    // needs a migrated database like the chron-db ones, eg.
+    // CHRON_TEST_DATABASE_URI=... cargo test -p chron-ingest -- --ignored
+    async fn connect() -> ChronDb {
+        let uri = std::env::var("CHRON_TEST_DATABASE_URI")
+            .expect("CHRON_TEST_DATABASE_URI needs to be set for the database tests");
+        let pool = PgPoolOptions::new()
+            .max_connections(2)
+            .connect(&uri)
+            .await
+            .unwrap();
+        ChronDb {
+            pool,
+            saved_objects: Default::default(),
+            partitions: Default::default(),
+        }
+    }
+
+    fn version(
+        entity_id: &str,
+        valid_from: OffsetDateTime,
+        last_seen: Option<OffsetDateTime>,
+        data: serde_json::Value,
+    ) -> MirrorVersion {
+        MirrorVersion {
+            entity_id: entity_id.to_string(),
+            valid_from: valid_from.into(),
+            last_seen: last_seen.map(Into::into),
+            data,
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs CHRON_TEST_DATABASE_URI"]
+    async fn replay_with_newer_last_seen() {
+        let db = connect().await;
+        let kind = EntityKind::Team;
+        let id = format!("mirrortest-{}", Uuid::new_v4());
+        let t0 =
+            OffsetDateTime::now_utc().replace_nanosecond(0).unwrap() - time::Duration::hours(3);
+        let t1 = t0 + time::Duration::hours(1);
+        let t2 = t0 + time::Duration::hours(2);
+
+        // first pass: the version was still the latest on the source
+        let first = vec![version(&id, t0, None, json!({"a": 1}))];
+        assert_eq!(save_page(&db, kind, first).await.unwrap(), 1);
+
+        // rescanned after the source moved on, same version with last_seen filled in
+        let replay = || {
+            vec![
+                version(&id, t0, Some(t1), json!({"a": 1})),
+                version(&id, t2, None, json!({"a": 2})),
+            ]
+        };
+        assert_eq!(save_page(&db, kind, replay()).await.unwrap(), 2);
+        assert_eq!(save_page(&db, kind, replay()).await.unwrap(), 0);
+
+        let versions: Vec<(OffsetDateTime, Option<OffsetDateTime>, Option<OffsetDateTime>)> =
+            sqlx::query_as("select valid_from, valid_to, last_seen from versions where kind = $1 and entity_id = $2 order by valid_from")
+                .bind(kind)
+                .bind(&id)
+                .fetch_all(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(versions, vec![(t0, Some(t2), Some(t1)), (t2, None, None)]);
+    }
+}
@@ -14,6 +14,9 @@ use uuid::Uuid;
 use crate::http::{ClientResponse, DataClient};
 use crate::models::{MmolbState, MmolbTime};
 
+pub mod archive;
+pub mod compaction;
+pub mod export;
 pub mod feeds;
 pub mod games;
 pub mod league;
@@ -21,7 +24,9 @@ pub mod maintenance;
 pub mod map;
 pub mod matviews;
 pub mod message;
+pub mod mirror;
 pub mod misc;
+pub mod verify;
 
 #[derive(Clone)]
 pub struct WorkerContext {
@@ -88,7 +93,6 @@ impl WorkerContext {
         let resp = self.client.fetch(url).await?;
         let entity_id = entity_id.into();
         self.db.save(resp.to_chron(kind, &entity_id)?).await?;
-        // self.scylla.save(resp.to_chron(kind, &entity_id)?).await?;
         Ok(resp)
     }
 
@@ -0,0 +1,196 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use chron_db::{json_hash, models::EntityKind};
+use futures::{StreamExt, TryStreamExt};
+use reqwest::Url;
+use serde::Deserialize;
+use sqlx::Row;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use super::{WorkerContext, archive};
+
+const CHUNK_SIZE: usize = 1000;
+
+// rebuild_entity's fake "lock" row in latest_versions points at this
+const LOCK_HASH: Uuid = Uuid::nil();
+
+// checks that every object still hashes to its key and that nothing points at a missing object.
+// with `repair`, bad/missing objects get pulled from an archive if given, otherwise mirror_url
+pub async fn verify(
+    ctx: &WorkerContext,
+    repair: bool,
+    archive_path: Option<&String>,
+) -> anyhow::Result<()> {
+    let mismatched = find_mismatched_objects(ctx).await?;
+    let dangling = find_dangling_refs(ctx).await?;
+
+    info!(
+        "{} objects don't match their hash, {} hashes are referenced but missing",
+        mismatched.len(),
+        dangling.len()
+    );
+    if !repair || (mismatched.is_empty() && dangling.is_empty()) {
+        return Ok(());
+    }
+
+    let broken = mismatched
+        .iter()
+        .chain(dangling.iter())
+        .copied()
+        .collect::<HashSet<_>>();
+    let found = match archive_path {
+        Some(path) => {
+            let path = Path::new(path).to_owned();
+            let wanted = broken.clone();
+            tokio::task::spawn_blocking(move || archive::find_objects(&path, &wanted)).await??
+        }
+        None => find_upstream(ctx, &broken).await?,
+    };
+
+    for (hash, data) in &found {
+        // the hash is already checked, so overwriting a mismatched one is safe
+        sqlx::query("insert into objects (hash, data) values ($1, $2) on conflict (hash) do update set data = excluded.data")
+            .bind(hash)
+            .bind(data)
+            .execute(&ctx.db.pool)
+            .await?;
+    }
+    info!("repaired {} of {} objects", found.len(), broken.len());
+
+    for hash in broken.iter().filter(|h| !found.contains_key(h)) {
+        warn!("couldn't find a copy of {}", hash);
+    }
+    Ok(())
+}
+
+async fn find_mismatched_objects(ctx: &WorkerContext) -> anyhow::Result<Vec<Uuid>> {
+    let parallelism = std::thread::available_parallelism()
+        .map(|x| x.get())
+        .unwrap_or(4);
+
+    let mut checked = 0;
+    let mut mismatched = Vec::new();
+    let mut chunks = sqlx::query("select hash, data::text as data from objects")
+        .fetch(&ctx.db.pool)
+        .try_chunks(CHUNK_SIZE)
+        .map(|chunk| async move {
+            let rows = chunk?;
+            // hashing is all cpu, keep it off the runtime
+            let res = tokio::task::spawn_blocking(move || check_hashes(rows)).await??;
+            anyhow::Ok(res)
+        })
+        .buffer_unordered(parallelism);
+
+    while let Some(res) = chunks.next().await {
+        let (count, bad) = res?;
+        checked += count;
+        mismatched.extend(bad);
+
+        if checked % (CHUNK_SIZE * 100) == 0 {
+            info!("verified {} objects so far", checked);
+        }
+    }
+
+    info!("verified {} objects", checked);
+    Ok(mismatched)
+}
+
+fn check_hashes(rows: Vec<sqlx::postgres::PgRow>) -> anyhow::Result<(usize, Vec<Uuid>)> {
+    let mut bad = Vec::new();
+    for row in &rows {
+        let hash: Uuid = row.try_get("hash")?;
+        let data: String = row.try_get("data")?;
+        let (actual, _) = json_hash(serde_json::from_str(&data)?)?;
+        if actual != hash {
+            error!("object {} hashes to {}", hash, actual);
+            bad.push(hash);
+        }
+    }
+    Ok((rows.len(), bad))
+}
+
+async fn find_dangling_refs(ctx: &WorkerContext) -> anyhow::Result<Vec<Uuid>> {
+    let mut dangling = HashSet::new();
+    for table in ["versions", "observations", "latest_versions"] {
+        let rows = sqlx::query(&format!(
+            "select t.kind, t.hash, count(*) as refs from {} t left join objects o using (hash) where o.hash is null and t.hash != $1 group by t.kind, t.hash",
+            table
+        ))
+        .bind(LOCK_HASH)
+        .fetch_all(&ctx.db.pool)
+        .await?;
+
+        for row in rows {
+            let kind: EntityKind = row.try_get("kind")?;
+            let hash: Uuid = row.try_get("hash")?;
+            let refs: i64 = row.try_get("refs")?;
+            error!(
+                "{} {:?} row(s) in {} point at missing object {}",
+                refs, kind, table, hash
+            );
+            dangling.insert(hash);
+        }
+    }
+    Ok(dangling.into_iter().collect())
+}
+
+#[derive(Deserialize)]
+struct UpstreamPage {
+    items: Vec<UpstreamVersion>,
+}
+
+#[derive(Deserialize)]
+struct UpstreamVersion {
+    data: serde_json::Value,
+}
+
+// the versions api doesn't look things up by hash, so fetch every version of whatever
+// referenced it and hash them until one matches
+async fn find_upstream(
+    ctx: &WorkerContext,
+    wanted: &HashSet<Uuid>,
+) -> anyhow::Result<HashMap<Uuid, serde_json::Value>> {
+    let Some(source) = &ctx.config.mirror_url else {
+        return Err(anyhow::anyhow!(
+            "no archive given and no mirror_url configured, nowhere to repair from"
+        ));
+    };
+    let source = source.trim_end_matches('/');
+
+    let hashes = wanted.iter().copied().collect::<Vec<_>>();
+    let entities: Vec<(EntityKind, String)> = sqlx::query_as(
+        "select distinct kind, entity_id from versions where hash = any($1) union select distinct kind, entity_id from observations where hash = any($1)",
+    )
+    .bind(&hashes)
+    .fetch_all(&ctx.db.pool)
+    .await?;
+
+    let mut found = HashMap::new();
+    for (kind, entity_id) in entities {
+        let kind_name = serde_json::to_value(kind)?;
+        let mut url = Url::parse(&format!("{}/chron/v0/versions", source))?;
+        url.query_pairs_mut()
+            .append_pair("kind", kind_name.as_str().unwrap_or_default())
+            .append_pair("id", &entity_id)
+            .append_pair("count", "1000");
+
+        let page: UpstreamPage = match ctx.client.fetch(url).await.and_then(|x| x.parse()) {
+            Ok(page) => page,
+            Err(e) => {
+                error!("error fetching {:?} {} upstream: {:?}", kind, entity_id, e);
+                continue;
+            }
+        };
+        for version in page.items {
+            let (hash, data) = json_hash(version.data)?;
+            if wanted.contains(&hash) {
+                found.insert(hash, data);
+            }
+        }
+    }
+    Ok(found)
+}